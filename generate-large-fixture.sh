@@ -0,0 +1,52 @@
+#!/bin/bash
+# Generates a large Markdown file for measuring chunked loading.
+# Usage: ./generate-large-fixture.sh [size in MB, default 50]
+# Open the result in the app and watch the "Streamed ... in ..." line in the log.
+
+SIZE_MB=${1:-50}
+OUT_DIR="test-fixtures"
+OUT_FILE="$OUT_DIR/large-${SIZE_MB}mb.md"
+TARGET_BYTES=$((SIZE_MB * 1024 * 1024))
+
+mkdir -p "$OUT_DIR"
+
+echo "📝 Generating $OUT_FILE (${SIZE_MB} MB)..."
+
+# Build one section of mixed Markdown, then repeat it until the target size is reached
+SECTION_FILE=$(mktemp)
+{
+    echo "## Section"
+    echo
+    echo "Lorem ipsum dolor sit amet, **consectetur** adipiscing elit, sed do _eiusmod_ tempor incididunt ut labore et dolore magna aliqua. Ümlauts, ñ and — dashes keep the UTF-8 decoder honest."
+    echo
+    echo "- First item with \`inline code\`"
+    echo "- Second item with a [link](https://example.com)"
+    echo "- Third item"
+    echo
+    echo '```rust'
+    echo 'fn main() { println!("hello"); }'
+    echo '```'
+    echo
+    echo "| Column A | Column B |"
+    echo "|----------|----------|"
+    echo "| 1        | 2        |"
+    echo
+} > "$SECTION_FILE"
+
+# Double the content until it is large enough, then cut it to size at a line
+# boundary, so no multi-byte UTF-8 sequence is split at the end
+BODY_FILE=$(mktemp)
+trap 'rm -f "$SECTION_FILE" "$BODY_FILE" "$BODY_FILE.next"' EXIT
+cp "$SECTION_FILE" "$BODY_FILE"
+while [ "$(wc -c < "$BODY_FILE")" -lt "$TARGET_BYTES" ]; do
+    cat "$BODY_FILE" "$BODY_FILE" > "$BODY_FILE.next"
+    mv "$BODY_FILE.next" "$BODY_FILE"
+done
+
+{
+    echo "# Large file fixture (${SIZE_MB} MB)"
+    echo
+    cat "$BODY_FILE"
+} | head -c "$TARGET_BYTES" | LC_ALL=C sed '$d' > "$OUT_FILE"
+
+echo "✅ Wrote $(wc -c < "$OUT_FILE") bytes to $OUT_FILE"
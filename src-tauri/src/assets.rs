@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// Upper bound on the assets watched for one document, so a page of links can't
+// use up the OS watch limit
+pub const MAX_WATCHED_ASSETS: usize = 256;
+
+/// Local files a Markdown document links to or embeds: inline links and images,
+/// reference definitions and the `src` of HTML tags. Relative targets are resolved
+/// against `base_dir`, the document's directory (`None` for untitled documents).
+/// URLs, anchors, code and files that don't exist are skipped.
+pub fn local_targets(markdown: &str, base_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut targets = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(*m)) {
+            fence = Some(marker);
+            continue;
+        }
+
+        let line = strip_code_spans(line);
+        let candidates = reference_definition(&line)
+            .into_iter()
+            .chain(inline_link_targets(&line))
+            .chain(html_src_attributes(&line));
+        for target in candidates {
+            let Some(path) = resolve(&target, base_dir) else { continue };
+            if path.is_file() && seen.insert(path.clone()) {
+                targets.push(path);
+                if targets.len() == MAX_WATCHED_ASSETS {
+                    return targets;
+                }
+            }
+        }
+    }
+    targets
+}
+
+/// Blank out `code spans`, whose contents are never links
+fn strip_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_code = false;
+    for c in line.chars() {
+        if c == '`' {
+            in_code = !in_code;
+        }
+        out.push(if in_code || c == '`' { ' ' } else { c });
+    }
+    out
+}
+
+/// Target of a reference definition: `[logo]: ./images/logo.png "Title"`
+fn reference_definition(line: &str) -> Option<String> {
+    let rest = line.trim_start().strip_prefix('[')?;
+    let (_, target) = rest.split_once("]:")?;
+    link_destination(target.trim_start())
+}
+
+/// Targets of `[text](target)` and `![alt](target "title")`
+fn inline_link_targets(line: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("](") {
+        rest = &rest[start + 2..];
+        if let Some(target) = link_destination(rest) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// The destination at the start of `text`: either `<with spaces>`, or everything up
+/// to whitespace or the `)` that balances the link's opening parenthesis
+fn link_destination(text: &str) -> Option<String> {
+    if let Some(bracketed) = text.strip_prefix('<') {
+        return bracketed.split_once('>').map(|(target, _)| target.to_string());
+    }
+    let mut depth = 0;
+    let end = text.char_indices()
+        .find(|(_, c)| match c {
+            '(' => {
+                depth += 1;
+                false
+            }
+            ')' if depth == 0 => true,
+            ')' => {
+                depth -= 1;
+                false
+            }
+            c => c.is_whitespace(),
+        })
+        .map_or(text.len(), |(i, _)| i);
+    Some(text[..end].to_string()).filter(|target| !target.is_empty())
+}
+
+/// Values of `src="…"` attributes, as used by `<img>`, `<video>` and `<source>`
+fn html_src_attributes(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("src=") {
+        rest = &rest[start + 4..];
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else { continue };
+        rest = &rest[1..];
+        if let Some(end) = rest.find(quote) {
+            values.push(rest[..end].to_string());
+            rest = &rest[end + 1..];
+        }
+    }
+    values
+}
+
+/// Turn a link target into a local path. `file://` URLs are accepted; other URLs
+/// and in-page anchors are not.
+fn resolve(target: &str, base_dir: Option<&Path>) -> Option<PathBuf> {
+    let target = target.trim();
+    let target = match target.strip_prefix("file://") {
+        Some(path) => path,
+        None if has_url_scheme(target) => return None,
+        None => target,
+    };
+    let target = target.split(['#', '?']).next()?;
+    if target.is_empty() {
+        return None;
+    }
+    let path = PathBuf::from(percent_decode(target));
+    let path = if path.is_absolute() { path } else { base_dir?.join(path) };
+    // Collecting the components drops `./` segments, so `a.png` and `./a.png` are one asset
+    Some(path.components().collect())
+}
+
+/// `https:`, `mailto:`, `data:` and the like. A single letter is a Windows drive.
+fn has_url_scheme(target: &str) -> bool {
+    target.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Decode `%20`-style escapes, which Markdown editors put in links to files with spaces
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mark-us-down-assets-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn finds_linked_files_that_exist() {
+        let dir = temp_dir("targets");
+        for name in ["logo.png", "my chart.svg", "clip.mp4", "notes.md"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let markdown = "\
+![logo](./logo.png \"Logo\") and [again](logo.png#top)
+![chart](my%20chart.svg)
+[notes]: notes.md
+<video src=\"clip.mp4\"></video>
+[site](https://example.com/logo.png) [missing](gone.png) `![code](notes.md)`
+";
+        let targets = local_targets(markdown, Some(&dir));
+        assert_eq!(targets, vec![
+            dir.join("logo.png"),
+            dir.join("my chart.svg"),
+            dir.join("notes.md"),
+            dir.join("clip.mp4"),
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_fenced_code() {
+        let dir = temp_dir("fences");
+        fs::write(dir.join("logo.png"), "").unwrap();
+        let markdown = "```\n![logo](logo.png)\n```\n~~~md\n![logo](logo.png)\n~~~\n";
+        assert!(local_targets(markdown, Some(&dir)).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_targets_need_a_directory() {
+        assert_eq!(resolve("logo.png", None), None);
+        assert_eq!(resolve("/images/logo.png", None), Some(PathBuf::from("/images/logo.png")));
+        assert_eq!(resolve("file:///images/logo.png", None), Some(PathBuf::from("/images/logo.png")));
+    }
+
+    #[test]
+    fn parses_link_destinations() {
+        assert_eq!(link_destination("<my file.png> \"title\")"), Some("my file.png".to_string()));
+        assert_eq!(link_destination("a(1).png)"), Some("a(1).png".to_string()));
+        assert_eq!(link_destination(")"), None);
+        assert!(has_url_scheme("mailto:someone@example.com"));
+        assert!(!has_url_scheme("C:/images/logo.png"));
+    }
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::documents::hash_bytes;
+
+// Directory inside the app data directory that holds central backups
+pub const BACKUPS_DIR: &str = "backups";
+
+/// Where backups of a document are kept
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupLocation {
+    /// Next to the file: `name.md~`, then `name.md.~1~`, `name.md.~2~`, …
+    #[default]
+    Tilde,
+    /// Hidden next to the file: `.name.md.bak`, then `.name.md.bak.1`, …
+    Hidden,
+    /// In the app's backups directory, one folder per document
+    Central,
+}
+
+/// Backup-on-save preferences, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub location: BackupLocation,
+    /// Number of rotated copies to keep
+    pub max_copies: usize,
+    /// Backups older than this are deleted on the next save; 0 keeps them regardless of age
+    pub max_age_days: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            enabled: false,
+            location: BackupLocation::Tilde,
+            max_copies: 5,
+            max_age_days: 30,
+        }
+    }
+}
+
+/// A backup of a document, as listed to the frontend. Slot 0 is the most recent.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    pub location: BackupLocation,
+    pub slot: usize,
+    pub modified_ms: Option<u64>,
+    pub size: u64,
+}
+
+/// Directory holding the backups of `path` for a location. Central backups are
+/// grouped by a hash of the document path so files with the same name don't collide.
+fn backup_dir(path: &Path, location: BackupLocation, central_dir: Option<&Path>) -> Option<PathBuf> {
+    match location {
+        BackupLocation::Tilde | BackupLocation::Hidden => path.parent().map(Path::to_path_buf),
+        BackupLocation::Central => {
+            let key = hash_bytes(path.to_string_lossy().as_bytes());
+            central_dir.map(|dir| dir.join(&key[..16]))
+        }
+    }
+}
+
+fn slot_name(file_name: &str, location: BackupLocation, slot: usize) -> String {
+    match (location, slot) {
+        (BackupLocation::Tilde, 0) => format!("{}~", file_name),
+        (BackupLocation::Tilde, n) => format!("{}.~{}~", file_name, n),
+        (BackupLocation::Hidden, 0) => format!(".{}.bak", file_name),
+        (BackupLocation::Hidden, n) => format!(".{}.bak.{}", file_name, n),
+        (BackupLocation::Central, 0) => format!("{}.bak", file_name),
+        (BackupLocation::Central, n) => format!("{}.bak.{}", file_name, n),
+    }
+}
+
+/// The slot number `candidate` stands for, if it is a backup of `file_name`
+fn parse_slot(file_name: &str, location: BackupLocation, candidate: &str) -> Option<usize> {
+    if candidate == slot_name(file_name, location, 0) {
+        return Some(0);
+    }
+    let numbered = match location {
+        BackupLocation::Tilde => candidate.strip_prefix(&format!("{}.~", file_name))?.strip_suffix('~')?,
+        BackupLocation::Hidden => candidate.strip_prefix(&format!(".{}.bak.", file_name))?,
+        BackupLocation::Central => candidate.strip_prefix(&format!("{}.bak.", file_name))?,
+    };
+    numbered.parse().ok().filter(|n| *n > 0)
+}
+
+/// Existing backups of `path` in one location, most recent slot first
+fn slots(path: &Path, location: BackupLocation, central_dir: Option<&Path>) -> Vec<(usize, PathBuf)> {
+    let (Some(dir), Some(file_name)) = (backup_dir(path, location, central_dir), path.file_name()) else {
+        return Vec::new();
+    };
+    let file_name = file_name.to_string_lossy();
+    let Ok(read_dir) = fs::read_dir(&dir) else { return Vec::new() };
+    let mut found: Vec<(usize, PathBuf)> = read_dir
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let slot = parse_slot(&file_name, location, &e.file_name().to_string_lossy())?;
+            Some((slot, e.path()))
+        })
+        .collect();
+    found.sort_by_key(|(slot, _)| *slot);
+    found
+}
+
+/// Copy the current disk version of `path` into backup slot 0, shifting older
+/// copies up and deleting those beyond `max_copies` or `max_age_days`.
+/// Returns the new backup, or `None` if backups are off or there is nothing to back up yet.
+pub fn create_backup(path: &Path, settings: &BackupSettings, central_dir: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    if !settings.enabled || settings.max_copies == 0 || !path.is_file() {
+        return Ok(None);
+    }
+    let location = settings.location;
+    let dir = backup_dir(path, location, central_dir)
+        .ok_or_else(|| io::Error::other("No backup directory available"))?;
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?
+        .to_string_lossy()
+        .to_string();
+    fs::create_dir_all(&dir)?;
+
+    // Rotate from the oldest slot down so nothing is overwritten
+    for (slot, existing) in slots(path, location, central_dir).into_iter().rev() {
+        if slot + 1 >= settings.max_copies {
+            fs::remove_file(&existing)?;
+        } else {
+            fs::rename(&existing, dir.join(slot_name(&file_name, location, slot + 1)))?;
+        }
+    }
+
+    let backup = dir.join(slot_name(&file_name, location, 0));
+    fs::copy(path, &backup)?;
+
+    if settings.max_age_days > 0 {
+        let max_age = Duration::from_secs(u64::from(settings.max_age_days) * 24 * 60 * 60);
+        for (slot, existing) in slots(path, location, central_dir) {
+            let expired = fs::metadata(&existing)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > max_age);
+            if slot > 0 && expired {
+                fs::remove_file(&existing)?;
+            }
+        }
+    }
+    Ok(Some(backup))
+}
+
+/// All backups of `path` across every location, so copies made before the
+/// location setting changed are still found
+pub fn list_backups(path: &Path, central_dir: Option<&Path>) -> Vec<BackupInfo> {
+    [BackupLocation::Tilde, BackupLocation::Hidden, BackupLocation::Central]
+        .into_iter()
+        .flat_map(|location| {
+            slots(path, location, central_dir).into_iter().filter_map(move |(slot, backup)| {
+                let meta = fs::metadata(&backup).ok()?;
+                let modified_ms = meta.modified().ok()
+                    .and_then(|t: SystemTime| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64);
+                Some(BackupInfo {
+                    path: backup.to_string_lossy().to_string(),
+                    location,
+                    slot,
+                    modified_ms,
+                    size: meta.len(),
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mark-us-down-backups-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn settings(location: BackupLocation, max_copies: usize) -> BackupSettings {
+        BackupSettings { enabled: true, location, max_copies, max_age_days: 0 }
+    }
+
+    /// Save `versions` in turn, backing up the disk version before each save
+    fn save_versions(path: &Path, versions: &[&str], settings: &BackupSettings, central_dir: Option<&Path>) {
+        for version in versions {
+            create_backup(path, settings, central_dir).unwrap();
+            fs::write(path, version).unwrap();
+        }
+    }
+
+    fn slot_contents(path: &Path, location: BackupLocation, central_dir: Option<&Path>) -> Vec<(usize, String)> {
+        slots(path, location, central_dir).into_iter()
+            .map(|(slot, backup)| (slot, fs::read_to_string(backup).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn rotates_and_drops_copies_beyond_the_limit() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("notes.md");
+        fs::write(&path, "v1").unwrap();
+        save_versions(&path, &["v2", "v3", "v4", "v5"], &settings(BackupLocation::Tilde, 3), None);
+        assert_eq!(slot_contents(&path, BackupLocation::Tilde, None), vec![
+            (0, "v4".to_string()),
+            (1, "v3".to_string()),
+            (2, "v2".to_string()),
+        ]);
+        assert!(dir.join("notes.md~").is_file());
+        assert!(dir.join("notes.md.~2~").is_file());
+        assert!(!dir.join("notes.md.~3~").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_hidden_and_central_backups() {
+        let dir = temp_dir("locations");
+        let central = dir.join(BACKUPS_DIR);
+        let path = dir.join("notes.md");
+        fs::write(&path, "v1").unwrap();
+        save_versions(&path, &["v2", "v3"], &settings(BackupLocation::Hidden, 2), None);
+        save_versions(&path, &["v4", "v5"], &settings(BackupLocation::Central, 2), Some(&central));
+        assert_eq!(slot_contents(&path, BackupLocation::Hidden, None), vec![(0, "v2".to_string()), (1, "v1".to_string())]);
+        assert_eq!(slot_contents(&path, BackupLocation::Central, Some(&central)), vec![(0, "v4".to_string()), (1, "v3".to_string())]);
+        assert_eq!(list_backups(&path, Some(&central)).len(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_to_back_up_before_the_first_save() {
+        let dir = temp_dir("new");
+        let path = dir.join("notes.md");
+        assert_eq!(create_backup(&path, &settings(BackupLocation::Tilde, 3), None).unwrap(), None);
+        fs::write(&path, "v1").unwrap();
+        assert_eq!(create_backup(&path, &BackupSettings::default(), None).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_only_backups_of_the_file() {
+        assert_eq!(parse_slot("a.md", BackupLocation::Tilde, "a.md~"), Some(0));
+        assert_eq!(parse_slot("a.md", BackupLocation::Tilde, "a.md.~12~"), Some(12));
+        assert_eq!(parse_slot("a.md", BackupLocation::Tilde, "a.md.~0~"), None);
+        assert_eq!(parse_slot("a.md", BackupLocation::Tilde, "ab.md~"), None);
+        assert_eq!(parse_slot("a.md", BackupLocation::Hidden, ".a.md.bak.2"), Some(2));
+        assert_eq!(parse_slot("a.md", BackupLocation::Central, "a.md.bak.x"), None);
+    }
+}
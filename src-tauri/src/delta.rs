@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+use crate::line_endings::{self, LineEnding};
+
+/// Replace `deleted_lines` whole lines starting at `start_line` (1-based, like
+/// editor line numbers) with `text`, which ends in a line break unless it runs to
+/// the end of the file. Deleting lines past the end of the document deletes to the end.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineEdit {
+    pub start_line: usize,
+    pub deleted_lines: usize,
+    pub text: String,
+}
+
+/// New content of a file that changed externally, as sent with `file-changed-externally`:
+/// edits against the version the window last acknowledged, or the whole text when
+/// that version isn't known or the edits wouldn't be any smaller.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExternalContent {
+    Full { content: String },
+    Delta { edits: Vec<LineEdit> },
+}
+
+impl ExternalContent {
+    pub fn between(base: Option<&str>, content: String) -> Self {
+        match base.map(|base| line_edits(base, &content)) {
+            Some(edits) if edits.iter().map(|edit| edit.text.len()).sum::<usize>() < content.len() => {
+                ExternalContent::Delta { edits }
+            }
+            _ => ExternalContent::Full { content },
+        }
+    }
+}
+
+/// Edits turning `old` into `new`: the lines between those both versions start and
+/// end with are replaced in one go, which is all an append or a rewritten table
+/// takes. Linear in the size of the texts, however different they are.
+/// Line breaks in the inserted text are LF; the editor converts them to its own.
+pub fn line_edits(old: &str, new: &str) -> Vec<LineEdit> {
+    // Count lines the way the editor does, where a lone CR also ends a line
+    let old = line_endings::normalize(old, LineEnding::Lf);
+    let new = line_endings::normalize(new, LineEnding::Lf);
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let common_prefix = old_lines.iter().zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let common_suffix = old_lines[common_prefix..].iter().rev()
+        .zip(new_lines[common_prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let deleted_lines = old_lines.len() - common_prefix - common_suffix;
+    let inserted = &new_lines[common_prefix..new_lines.len() - common_suffix];
+    if deleted_lines == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+    vec![LineEdit {
+        start_line: common_prefix + 1,
+        deleted_lines,
+        text: inserted.concat(),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start_line: usize, deleted_lines: usize, text: &str) -> LineEdit {
+        LineEdit { start_line, deleted_lines, text: text.to_string() }
+    }
+
+    #[test]
+    fn appends_to_a_file_without_trailing_newline() {
+        assert_eq!(line_edits("a\nb", "a\nb\nc"), vec![edit(2, 1, "b\nc")]);
+    }
+
+    #[test]
+    fn changes_a_last_line_without_trailing_newline() {
+        assert_eq!(line_edits("a\nb\nc", "a\nb\nd"), vec![edit(3, 1, "d")]);
+        assert_eq!(line_edits("a\nb\nc", "a\nb\n"), vec![edit(3, 1, "")]);
+    }
+
+    #[test]
+    fn replaces_lines_between_common_prefix_and_suffix() {
+        assert_eq!(line_edits("a\nb\nc", "a\nx\ny\nc"), vec![edit(2, 1, "x\ny\n")]);
+        assert_eq!(line_edits("a\nb\nc", "a\nc"), vec![edit(2, 1, "")]);
+    }
+
+    #[test]
+    fn no_edits_for_the_same_text() {
+        assert!(line_edits("a\r\nb", "a\nb").is_empty());
+    }
+
+    #[test]
+    fn sends_whole_text_when_edits_are_not_smaller() {
+        assert!(matches!(ExternalContent::between(None, "a\n".to_string()), ExternalContent::Full { .. }));
+        assert!(matches!(ExternalContent::between(Some("x\n"), "a\n".to_string()), ExternalContent::Full { .. }));
+        assert!(matches!(
+            ExternalContent::between(Some("a\nb\n"), "a\nb\nc\n".to_string()),
+            ExternalContent::Delta { .. }
+        ));
+    }
+}
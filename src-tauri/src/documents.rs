@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use crate::encoding::{self, TextEncoding};
+use crate::line_endings::{self, LineEnding, LineEndingInfo};
+use crate::views::ViewState;
+
+/// Snapshot of a file's on-disk state, taken whenever the app loads or saves it.
+/// Two fingerprints describe the same content when size and hash agree; the
+/// modification time is informational, since touching a file doesn't change it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    pub modified_ms: Option<u64>,
+    pub size: u64,
+    pub hash: String,
+}
+
+impl Fingerprint {
+    /// Fingerprint bytes that were just read from or written to a file with the given metadata
+    pub fn from_bytes(bytes: &[u8], meta: Option<&fs::Metadata>) -> Self {
+        Fingerprint::from_hash(hash_bytes(bytes), bytes.len() as u64, meta)
+    }
+
+    /// Fingerprint from a hash computed elsewhere, e.g. while streaming a large file
+    pub fn from_hash(hash: String, size: u64, meta: Option<&fs::Metadata>) -> Self {
+        let modified_ms = meta
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64);
+        Fingerprint { modified_ms, size, hash }
+    }
+
+    pub fn same_content(&self, other: &Fingerprint) -> bool {
+        self.size == other.size && self.hash == other.hash
+    }
+}
+
+/// Hex-encoded SHA-256 of `bytes`
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Why a document can't be saved back to its file
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadOnly {
+    /// The user lacks write permission, or the file is locked by another process
+    NotWritable,
+    /// Opened with "Open Read-Only" or `--readonly`, e.g. for reference documents
+    Requested,
+    /// Only the first part of a large file was loaded, so saving would truncate it
+    Partial,
+}
+
+/// Whether the file at `path` can be opened for writing right now. Opening without
+/// truncating tests permissions, read-only mounts and locks without touching the file.
+pub fn is_writable(path: &Path) -> bool {
+    fs::OpenOptions::new().write(true).open(path).is_ok()
+}
+
+/// Identity of the file at `path`, for telling whether two paths open the same
+/// document: its canonical path with symlinks resolved, folded to lower case on
+/// platforms whose filesystems ignore case by default. A path that can't be
+/// resolved is used as given.
+pub fn canonical_key(path: &Path) -> String {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let key = canonical.to_string_lossy().to_string();
+    if cfg!(any(windows, target_os = "macos")) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// What the backend knows about an open document
+#[derive(Debug, Clone)]
+pub struct DocumentState {
+    pub path: String,
+    pub fingerprint: Fingerprint,
+    pub encoding: TextEncoding,
+    pub line_ending: LineEnding,
+    pub read_only: Option<ReadOnly>,
+    /// Text the editor was last given or saved, the base for merging external
+    /// changes into unsaved edits. `None` when the backend never held the whole text.
+    pub base: Option<Arc<str>>,
+}
+
+impl DocumentState {
+    pub fn loaded(path: &str, loaded: &LoadedDocument) -> Self {
+        DocumentState {
+            path: path.to_string(),
+            fingerprint: loaded.fingerprint.clone(),
+            encoding: loaded.encoding.clone(),
+            line_ending: loaded.line_endings.dominant,
+            read_only: loaded.read_only,
+            base: Some(Arc::from(loaded.content.as_str())),
+        }
+    }
+}
+
+/// Document metadata sent to the frontend alongside the content
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentInfo {
+    pub encoding: TextEncoding,
+    pub line_endings: LineEndingInfo,
+    /// Content comes from the crash-recovery journal rather than the file on disk
+    pub recovered: bool,
+    /// Content has changes not saved to the file, as when recovered or when a tab
+    /// with unsaved changes moves to another window
+    pub unsaved: bool,
+    /// Set when the editor should lock the document
+    pub read_only: Option<ReadOnly>,
+    /// How the document was shown when it was last closed
+    pub view: Option<ViewState>,
+}
+
+// Document state per document id, recorded on every load and save.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+pub struct OpenDocuments(pub Arc<Mutex<HashMap<String, DocumentState>>>);
+impl Default for OpenDocuments {
+    fn default() -> Self { OpenDocuments(Arc::new(Mutex::new(HashMap::new()))) }
+}
+
+impl OpenDocuments {
+    pub fn record(&self, document: &str, state: DocumentState) {
+        self.0.lock().unwrap().insert(document.to_string(), state);
+    }
+
+    pub fn get(&self, document: &str) -> Option<DocumentState> {
+        self.0.lock().unwrap().get(document).cloned()
+    }
+
+    pub fn remove(&self, document: &str) {
+        self.0.lock().unwrap().remove(document);
+    }
+
+    /// Id of a document other than `except` that is the file at `path`
+    pub fn document_showing(&self, path: &Path, except: Option<&str>) -> Option<String> {
+        let key = canonical_key(path);
+        let documents = self.0.lock().unwrap().clone();
+        documents.into_iter()
+            .filter(|(document, _)| Some(document.as_str()) != except)
+            .find(|(_, doc)| canonical_key(Path::new(&doc.path)) == key)
+            .map(|(document, _)| document)
+    }
+}
+
+/// One open document as the backend knows it, as listed by `list_documents`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentEntry {
+    pub id: String,
+    /// Label of the window its tab is in
+    pub window: String,
+    /// Its tab is the one in front of the window
+    pub active: bool,
+    /// `None` for untitled documents
+    pub path: Option<String>,
+    /// Identity of the file, as `canonical_key` gives it
+    pub canonical_path: Option<String>,
+    /// Has changes not saved to the file, as reported by the frontend
+    pub dirty: bool,
+    pub encoding: Option<TextEncoding>,
+    pub read_only: Option<ReadOnly>,
+    /// Hash of the file content as last loaded or saved
+    pub saved_hash: Option<String>,
+}
+
+impl DocumentEntry {
+    pub fn new(id: &str, window: &str, active: bool, state: Option<DocumentState>, dirty: bool) -> Self {
+        DocumentEntry {
+            id: id.to_string(),
+            window: window.to_string(),
+            active,
+            canonical_path: state.as_ref().map(|doc| canonical_key(Path::new(&doc.path))),
+            encoding: state.as_ref().map(|doc| doc.encoding.clone()),
+            read_only: state.as_ref().and_then(|doc| doc.read_only),
+            saved_hash: state.as_ref().map(|doc| doc.fingerprint.hash.clone()),
+            path: state.map(|doc| doc.path),
+            dirty,
+        }
+    }
+}
+
+/// A document read from disk along with the fingerprint of the bytes it came from
+pub struct LoadedDocument {
+    pub content: String,
+    pub fingerprint: Fingerprint,
+    pub encoding: TextEncoding,
+    pub line_endings: LineEndingInfo,
+    pub read_only: Option<ReadOnly>,
+}
+
+impl LoadedDocument {
+    fn new(path: &Path, content: String, fingerprint: Fingerprint, encoding: TextEncoding) -> Self {
+        let line_endings = line_endings::detect(&content);
+        let read_only = (!is_writable(path)).then_some(ReadOnly::NotWritable);
+        LoadedDocument { content, fingerprint, encoding, line_endings, read_only }
+    }
+
+    /// Lock the document on request. A file that isn't writable anyway keeps that reason.
+    pub fn with_read_only(mut self, requested: bool) -> Self {
+        if requested && self.read_only.is_none() {
+            self.read_only = Some(ReadOnly::Requested);
+        }
+        self
+    }
+
+    pub fn info(&self) -> DocumentInfo {
+        DocumentInfo {
+            encoding: self.encoding.clone(),
+            line_endings: self.line_endings,
+            recovered: false,
+            unsaved: false,
+            read_only: self.read_only,
+            view: None,
+        }
+    }
+}
+
+/// Read a document, detect its encoding and fingerprint it in one pass, so the
+/// fingerprint always matches exactly the bytes behind the content handed to the editor.
+pub fn load_document(path: &Path) -> io::Result<LoadedDocument> {
+    let bytes = fs::read(path)?;
+    let meta = fs::metadata(path).ok();
+    let fingerprint = Fingerprint::from_bytes(&bytes, meta.as_ref());
+    let decoded = encoding::detect_and_decode(&bytes);
+    Ok(LoadedDocument::new(path, decoded.content, fingerprint, decoded.encoding))
+}
+
+/// Read a document with an encoding chosen by the user instead of the detected one
+pub fn load_document_with_encoding(path: &Path, text_encoding: &TextEncoding) -> io::Result<LoadedDocument> {
+    let bytes = fs::read(path)?;
+    let meta = fs::metadata(path).ok();
+    let fingerprint = Fingerprint::from_bytes(&bytes, meta.as_ref());
+    let decoded = encoding::decode_with(&bytes, text_encoding);
+    Ok(LoadedDocument::new(path, decoded.content, fingerprint, decoded.encoding))
+}
+
+/// Ways the user can resolve a save conflict
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictResolution {
+    Overwrite,
+    SaveAs,
+    Merge,
+}
+
+/// Returned instead of saving when the file on disk no longer matches what was loaded.
+/// `disk_content` is the current disk version so the frontend can offer a merge.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveConflict {
+    pub path: String,
+    pub expected: Fingerprint,
+    pub actual: Fingerprint,
+    pub disk_content: Option<String>,
+    pub resolutions: Vec<ConflictResolution>,
+}
+
+/// Compare the disk state of `path` with the fingerprint recorded at load time.
+/// A file that has disappeared is not a conflict: saving simply recreates it.
+pub fn check_for_conflict(path: &Path, expected: &Fingerprint) -> io::Result<Option<SaveConflict>> {
+    // Fingerprint and decode the same bytes, so the content shown matches `actual`
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let meta = fs::metadata(path).ok();
+    let actual = Fingerprint::from_bytes(&bytes, meta.as_ref());
+    if actual.same_content(expected) {
+        return Ok(None);
+    }
+    Ok(Some(SaveConflict {
+        path: path.to_string_lossy().to_string(),
+        expected: expected.clone(),
+        actual,
+        disk_content: Some(encoding::detect_and_decode(&bytes).content),
+        resolutions: vec![ConflictResolution::Overwrite, ConflictResolution::SaveAs, ConflictResolution::Merge],
+    }))
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Extensions of the files the app opens
+const MARKDOWN_EXTENSIONS: [&str; 3] = ["md", "markdown", "txt"];
+
+// How deep into a dropped folder files are looked for
+const MAX_DEPTH: usize = 16;
+
+// How many folder entries a drop looks at before it gives up, so dropping a
+// home folder doesn't walk the whole disk
+const MAX_ENTRIES: usize = 50_000;
+
+/// How dropped folders are opened, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DropSettings {
+    /// Dropped folders holding more Markdown files than this need confirmation
+    /// before they are opened
+    pub confirm_folder_files_above: usize,
+}
+
+impl Default for DropSettings {
+    fn default() -> Self {
+        DropSettings { confirm_folder_files_above: 20 }
+    }
+}
+
+/// The files to open for what was dropped on a window
+#[derive(Debug, Default)]
+pub struct DroppedFiles {
+    pub files: Vec<PathBuf>,
+    /// How many of `files` were found inside dropped folders
+    pub from_folders: usize,
+    /// Whether looking inside the folders stopped before it had been through them
+    pub stopped_early: bool,
+}
+
+/// Whether `path` has the extension of a file the app opens, in any case
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| MARKDOWN_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known)))
+}
+
+/// Keep the dropped Markdown files and replace each dropped folder with the
+/// Markdown files inside it, in the order they were dropped. With `stop_above`,
+/// stops looking once the folders hold more files than that.
+pub fn expand(paths: &[PathBuf], stop_above: Option<usize>) -> DroppedFiles {
+    let mut walk = Walk { files: Vec::new(), from_folders: 0, entries: 0, stop_above, stopped: false };
+    for path in paths {
+        if path.is_dir() {
+            walk.collect_markdown_files(path, 0);
+        } else if is_markdown_file(path) {
+            walk.files.push(path.clone());
+        }
+        if walk.stopped {
+            break;
+        }
+    }
+    DroppedFiles { files: walk.files, from_folders: walk.from_folders, stopped_early: walk.stopped }
+}
+
+struct Walk {
+    files: Vec<PathBuf>,
+    from_folders: usize,
+    entries: usize,
+    stop_above: Option<usize>,
+    stopped: bool,
+}
+
+impl Walk {
+    /// Add the Markdown files under `dir` to `files`, sorted by name within each folder.
+    /// Hidden entries are skipped, and symlinked folders aren't followed so a link
+    /// back up the tree can't loop.
+    fn collect_markdown_files(&mut self, dir: &Path, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        let mut entries: Vec<fs::DirEntry> = entries.filter_map(Result::ok)
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            self.entries += 1;
+            if self.entries > MAX_ENTRIES {
+                self.stopped = true;
+            }
+            if self.stopped {
+                return;
+            }
+            let Ok(file_type) = entry.file_type() else { continue };
+            let path = entry.path();
+            if file_type.is_dir() {
+                self.collect_markdown_files(&path, depth + 1);
+            } else if is_markdown_file(&path) {
+                self.files.push(path);
+                self.from_folders += 1;
+                if self.stop_above.is_some_and(|limit| self.from_folders > limit) {
+                    self.stopped = true;
+                }
+            }
+        }
+    }
+}
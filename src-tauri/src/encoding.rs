@@ -0,0 +1,228 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+// How much of a file is inspected when guessing BOM-less UTF-16
+const UTF16_SNIFF_LEN: usize = 4096;
+
+// Encodings offered by "Reopen with Encoding…" / "Save with Encoding…"
+pub const SUPPORTED_ENCODINGS: &[&str] = &[
+    "UTF-8",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-2",
+    "ISO-8859-15",
+    "windows-1250",
+    "windows-1251",
+    "KOI8-R",
+    "macintosh",
+    "Shift_JIS",
+    "EUC-JP",
+    "GBK",
+    "Big5",
+    "EUC-KR",
+];
+
+/// The character encoding a document was read with, reused when it is saved
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEncoding {
+    /// WHATWG encoding name, e.g. "UTF-8", "UTF-16LE" or "windows-1252"
+    pub name: String,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        TextEncoding { name: UTF_8.name().to_string(), bom: false }
+    }
+}
+
+impl TextEncoding {
+    fn new(encoding: &'static Encoding, bom: bool) -> Self {
+        TextEncoding { name: encoding.name().to_string(), bom }
+    }
+
+    /// Parse a user-supplied encoding label ("utf-8", "latin1", "cp1252", …)
+    pub fn from_label(label: &str, bom: bool) -> Option<Self> {
+        Encoding::for_label_no_replacement(label.trim().as_bytes()).map(|enc| TextEncoding::new(enc, bom))
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        Encoding::for_label(self.name.as_bytes()).unwrap_or(UTF_8)
+    }
+}
+
+/// Text decoded from raw file bytes
+pub struct Decoded {
+    pub content: String,
+    pub encoding: TextEncoding,
+}
+
+/// Guess UTF-16 without a BOM from the pattern of NUL bytes: mostly-ASCII text
+/// in UTF-16LE has a zero in every odd byte, UTF-16BE in every even byte.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SNIFF_LEN)];
+    if sample.len() < 4 || sample.len() % 2 == 1 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    if odd_zeros * 10 >= pairs * 4 && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 >= pairs * 4 && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Detect the encoding of `bytes` and decode them.
+///
+/// A byte order mark wins; otherwise BOM-less UTF-16 is recognised from its NUL
+/// pattern, valid UTF-8 is taken as UTF-8, and anything else is handed to
+/// chardetng to pick the most likely legacy code page (usually windows-1252).
+pub fn detect_and_decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (content, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Decoded { content: content.into_owned(), encoding: TextEncoding::new(encoding, true) };
+    }
+
+    // NUL bytes are valid UTF-8, so BOM-less UTF-16 has to be ruled out first
+    if let Some(encoding) = sniff_utf16(bytes) {
+        let (content, had_errors) = encoding.decode_without_bom_handling(bytes);
+        if !had_errors {
+            return Decoded { content: content.into_owned(), encoding: TextEncoding::new(encoding, false) };
+        }
+    }
+
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return Decoded { content: content.to_string(), encoding: TextEncoding::default() };
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, false);
+    let (content, _) = encoding.decode_without_bom_handling(bytes);
+    println!("Detected legacy encoding {} for non-UTF-8 file", encoding.name());
+    Decoded { content: content.into_owned(), encoding: TextEncoding::new(encoding, false) }
+}
+
+/// Detect the encoding of a file from its first bytes, for files decoded in chunks.
+/// Follows the same rules as `detect_and_decode`, allowing for a multi-byte UTF-8
+/// sequence cut off at the end of the sample. Returns the length of any BOM.
+pub fn detect_prefix(sample: &[u8]) -> (TextEncoding, usize) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(sample) {
+        return (TextEncoding::new(encoding, true), bom_len);
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return (TextEncoding::new(encoding, false), 0);
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return (TextEncoding::default(), 0),
+        Err(e) if e.error_len().is_none() => return (TextEncoding::default(), 0),
+        Err(_) => {}
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, false);
+    (TextEncoding::new(detector.guess(None, false), false), 0)
+}
+
+/// Decode `bytes` with an explicitly chosen encoding ("Reopen with Encoding…").
+/// A matching BOM is stripped; undecodable bytes become U+FFFD.
+pub fn decode_with(bytes: &[u8], encoding: &TextEncoding) -> Decoded {
+    let enc = encoding.encoding();
+    let (body, has_bom) = match Encoding::for_bom(bytes) {
+        Some((bom_enc, bom_len)) if bom_enc == enc => (&bytes[bom_len..], true),
+        _ => (bytes, false),
+    };
+    let (content, _) = enc.decode_without_bom_handling(body);
+    Decoded { content: content.into_owned(), encoding: TextEncoding::new(enc, has_bom || encoding.bom) }
+}
+
+/// Encode `content` for writing to disk. Fails instead of silently substituting
+/// characters the target code page cannot represent.
+pub fn encode(content: &str, encoding: &TextEncoding) -> io::Result<Vec<u8>> {
+    let enc = encoding.encoding();
+
+    // encoding_rs only decodes UTF-16, so it is encoded by hand
+    if enc == UTF_16LE || enc == UTF_16BE {
+        let mut bytes = Vec::with_capacity(content.len() * 2 + 2);
+        let little_endian = enc == UTF_16LE;
+        let units = encoding.bom.then_some(0xFEFF_u16).into_iter().chain(content.encode_utf16());
+        for unit in units {
+            let pair = if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+            bytes.extend_from_slice(&pair);
+        }
+        return Ok(bytes);
+    }
+
+    if enc == UTF_8 {
+        let mut bytes = Vec::with_capacity(content.len() + 3);
+        if encoding.bom {
+            bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        bytes.extend_from_slice(content.as_bytes());
+        return Ok(bytes);
+    }
+
+    let (bytes, _, unmappable) = enc.encode(content);
+    if unmappable {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the document contains characters that cannot be saved as {}", enc.name()),
+        ));
+    }
+    Ok(bytes.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn sniffs_utf16_without_bom() {
+        let decoded = detect_and_decode(&utf16("# Notes\nsome text\n", true));
+        assert_eq!(decoded.encoding, TextEncoding { name: "UTF-16LE".to_string(), bom: false });
+        assert_eq!(decoded.content, "# Notes\nsome text\n");
+
+        let decoded = detect_and_decode(&utf16("# Notes\nsome text\n", false));
+        assert_eq!(decoded.encoding, TextEncoding { name: "UTF-16BE".to_string(), bom: false });
+        assert_eq!(decoded.content, "# Notes\nsome text\n");
+    }
+
+    #[test]
+    fn utf8_is_not_taken_for_utf16() {
+        let decoded = detect_and_decode("plain ascii text\n".as_bytes());
+        assert_eq!(decoded.encoding, TextEncoding::default());
+        assert_eq!(detect_prefix("plain ascii text\n".as_bytes()), (TextEncoding::default(), 0));
+    }
+
+    #[test]
+    fn utf16_round_trips_with_bom() {
+        let encoding = TextEncoding::from_label("utf-16le", true).unwrap();
+        let bytes = encode("héllo", &encoding).unwrap();
+        assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+        let decoded = detect_and_decode(&bytes);
+        assert_eq!(decoded.encoding, encoding);
+        assert_eq!(decoded.content, "héllo");
+    }
+
+    #[test]
+    fn refuses_unencodable_characters() {
+        let encoding = TextEncoding::from_label("windows-1252", false).unwrap();
+        assert_eq!(encode("café €5", &encoding).unwrap(), b"caf\xE9 \x805");
+        let err = encode("日本語", &encoding).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::documents::{ReadOnly, SaveConflict};
+use crate::file_io::{SaveError, SaveStep};
+
+/// What went wrong, so the frontend can choose a recovery instead of showing raw text
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    IsDirectory,
+    InvalidEncoding,
+    TooLarge,
+    Conflict,
+    ReadOnly,
+    SaveFailed,
+    WatcherFailed,
+    WindowFailed,
+    InvalidArgument,
+    Io,
+}
+
+/// Error type shared by every Tauri command. Serialized as
+/// `{ kind, message, path, osError, ... }` so the frontend can switch on `kind`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub path: Option<String>,
+    pub os_error: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_step: Option<SaveStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<SaveConflict>,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        AppError {
+            kind,
+            message: message.into(),
+            path: None,
+            os_error: None,
+            save_step: None,
+            conflict: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    /// Classify an I/O error that happened while accessing `path`
+    pub fn io(err: io::Error, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::InvalidData => ErrorKind::InvalidEncoding,
+            // io::ErrorKind::IsADirectory needs a newer toolchain than we support
+            _ if path.is_dir() => ErrorKind::IsDirectory,
+            _ => ErrorKind::Io,
+        };
+        let mut error = AppError::new(kind, err.to_string()).with_path(path);
+        error.os_error = err.raw_os_error();
+        error
+    }
+
+    pub fn not_found(path: impl AsRef<Path>) -> Self {
+        AppError::new(ErrorKind::NotFound, "File does not exist").with_path(path)
+    }
+
+    pub fn watcher(message: impl fmt::Display, path: impl AsRef<Path>) -> Self {
+        AppError::new(ErrorKind::WatcherFailed, message.to_string()).with_path(path)
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        AppError::new(ErrorKind::InvalidArgument, message)
+    }
+
+    pub fn read_only(reason: ReadOnly, path: impl AsRef<Path>) -> Self {
+        let message = match reason {
+            ReadOnly::NotWritable => "You don't have permission to write to this file, or it is locked",
+            ReadOnly::Requested => "The document was opened read-only",
+            ReadOnly::Partial => "Only the beginning of this file was loaded, so it can't be saved over the original",
+        };
+        AppError::new(ErrorKind::ReadOnly, message).with_path(path)
+    }
+
+    pub fn conflict(conflict: SaveConflict) -> Self {
+        let mut error = AppError::new(ErrorKind::Conflict, "The file was changed on disk since it was opened")
+            .with_path(&conflict.path);
+        error.conflict = Some(conflict);
+        error
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<SaveError> for AppError {
+    fn from(e: SaveError) -> Self {
+        let kind = match e.io_kind {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            _ if e.step == SaveStep::Encode => ErrorKind::InvalidEncoding,
+            _ => ErrorKind::SaveFailed,
+        };
+        AppError {
+            kind,
+            message: e.to_string(),
+            path: Some(e.path),
+            os_error: e.os_error,
+            save_step: Some(e.step),
+            conflict: None,
+        }
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::new(ErrorKind::WindowFailed, e.to_string())
+    }
+}
+
+/// Payload of the `file-error` event, emitted for failures in callbacks
+/// (dialogs, drag and drop) that have no command result to return them through
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileErrorEvent {
+    pub operation: &'static str,
+    pub error: AppError,
+}
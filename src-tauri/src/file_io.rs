@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Counter that keeps temp file names unique when several saves run concurrently
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Upper bound on symlink hops followed when resolving the real save target
+const MAX_SYMLINK_DEPTH: usize = 32;
+
+/// The stage of an atomic save that failed
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SaveStep {
+    CheckConflict,
+    Encode,
+    ResolveTarget,
+    CreateTemp,
+    Write,
+    CopyPermissions,
+    Sync,
+    Rename,
+    SyncDirectory,
+}
+
+impl fmt::Display for SaveStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SaveStep::CheckConflict => "checking the file on disk for changes",
+            SaveStep::Encode => "encoding the document",
+            SaveStep::ResolveTarget => "resolving the save target",
+            SaveStep::CreateTemp => "creating the temporary file",
+            SaveStep::Write => "writing the temporary file",
+            SaveStep::CopyPermissions => "copying file permissions",
+            SaveStep::Sync => "flushing the temporary file to disk",
+            SaveStep::Rename => "replacing the original file",
+            SaveStep::SyncDirectory => "flushing the parent directory",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Error returned to the frontend when a save does not complete.
+/// The original file is left untouched for every step before `Rename`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveError {
+    pub path: String,
+    pub step: SaveStep,
+    pub message: String,
+    pub os_error: Option<i32>,
+    #[serde(skip)]
+    pub io_kind: io::ErrorKind,
+}
+
+impl SaveError {
+    pub(crate) fn new(path: &Path, step: SaveStep, err: io::Error) -> Self {
+        SaveError {
+            path: path.to_string_lossy().to_string(),
+            step,
+            message: err.to_string(),
+            os_error: err.raw_os_error(),
+            io_kind: err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to save {} while {}: {}", self.path, self.step, self.message)
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Follow symlinks from `path` to the file that should actually be replaced,
+/// so saving through a link updates its target instead of replacing the link.
+/// Dangling links resolve to their (not yet existing) target.
+fn resolve_symlink_target(path: &Path) -> io::Result<PathBuf> {
+    let mut target = path.to_path_buf();
+    for _ in 0..MAX_SYMLINK_DEPTH {
+        match fs::symlink_metadata(&target) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let link = fs::read_link(&target)?;
+                target = if link.is_absolute() {
+                    link
+                } else {
+                    target.parent().unwrap_or_else(|| Path::new("")).join(link)
+                };
+            }
+            Ok(_) => return Ok(target),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(target),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::other("too many levels of symbolic links"))
+}
+
+/// Build a hidden temp file path in the same directory as `target`, so the
+/// final rename never crosses a filesystem boundary.
+fn temp_path_for(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "untitled".to_string());
+    let unique = TEMP_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let temp_name = format!(".{}.{}-{}.tmp", file_name, std::process::id(), unique);
+    target.with_file_name(temp_name)
+}
+
+/// Give the temp file the mode and, where permitted, the owner of the file it replaces
+fn copy_metadata(temp_file: &File, original: &fs::Metadata) -> io::Result<()> {
+    temp_file.set_permissions(original.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let current = temp_file.metadata()?;
+        if current.uid() != original.uid() || current.gid() != original.gid() {
+            // Only root may hand a file to another user. Keep the group at least,
+            // which is what matters for shared-folder setups.
+            if let Err(e) = std::os::unix::fs::fchown(temp_file, Some(original.uid()), Some(original.gid())) {
+                println!("Could not preserve file owner ({}); keeping group only", e);
+                if let Err(e) = std::os::unix::fs::fchown(temp_file, None, Some(original.gid())) {
+                    println!("Could not preserve file group: {}", e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Flush the directory entry so the rename itself survives a power cut
+#[cfg(unix)]
+fn sync_parent_dir(target: &Path) -> io::Result<()> {
+    match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_target: &Path) -> io::Result<()> {
+    // Directory handles cannot be fsynced on Windows; MoveFileEx is already durable
+    // once the data in the renamed file has been flushed.
+    Ok(())
+}
+
+/// Write `contents` to `path` atomically: the data goes to a sibling temp file,
+/// is fsynced, and is then renamed over the target. Readers and crashes see either
+/// the old file or the complete new one, never a truncated mix.
+///
+/// Symlinks are followed so the link target is updated, and the existing file's
+/// permissions (and ownership on Unix, where allowed) are carried over.
+pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    let target = resolve_symlink_target(path)
+        .map_err(|e| SaveError::new(path, SaveStep::ResolveTarget, e))?;
+    let original_meta = fs::metadata(&target).ok();
+    let temp_path = temp_path_for(&target);
+
+    let mut temp_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+        .map_err(|e| SaveError::new(path, SaveStep::CreateTemp, e))?;
+
+    // Everything after this point must clean up the temp file on failure
+    let result = (|| {
+        temp_file
+            .write_all(contents)
+            .map_err(|e| SaveError::new(path, SaveStep::Write, e))?;
+        if let Some(meta) = &original_meta {
+            copy_metadata(&temp_file, meta)
+                .map_err(|e| SaveError::new(path, SaveStep::CopyPermissions, e))?;
+        }
+        temp_file
+            .sync_all()
+            .map_err(|e| SaveError::new(path, SaveStep::Sync, e))?;
+        Ok(())
+    })();
+
+    drop(temp_file);
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    if let Err(e) = fs::rename(&temp_path, &target) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(SaveError::new(path, SaveStep::Rename, e));
+    }
+
+    sync_parent_dir(&target).map_err(|e| SaveError::new(path, SaveStep::SyncDirectory, e))?;
+
+    Ok(())
+}
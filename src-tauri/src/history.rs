@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::documents::{canonical_key, hash_bytes};
+use crate::file_io;
+
+// Directory inside the app data directory that holds the local history store
+pub const HISTORY_DIR: &str = "history";
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+const MB: u64 = 1024 * 1024;
+
+/// Local history preferences, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    /// Unlabeled snapshots kept per file; labeled checkpoints don't count
+    pub max_snapshots: usize,
+    /// Unlabeled snapshots older than this are pruned; 0 keeps them regardless of age
+    pub max_age_days: u32,
+    /// Files larger than this get no snapshots, as each one is a full copy
+    pub max_file_mb: u64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings {
+            enabled: true,
+            max_snapshots: 50,
+            max_age_days: 30,
+            max_file_mb: 5,
+        }
+    }
+}
+
+/// What caused a snapshot to be taken
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SnapshotSource {
+    Save,
+    External,
+}
+
+/// One version of a file in its history timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub id: String,
+    /// SHA-256 of the content, naming its object in the store
+    pub hash: String,
+    pub timestamp_ms: u64,
+    pub size: u64,
+    pub source: SnapshotSource,
+    /// Set when the user marks the snapshot as a named checkpoint
+    pub label: Option<String>,
+}
+
+/// Timeline of one file, stored as `index/<key>.json`, oldest snapshot first
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryIndex {
+    path: String,
+    snapshots: Vec<Snapshot>,
+}
+
+/// Content-addressed snapshot store. Snapshot text lives once per distinct content
+/// under `objects/`, and each file has an index of the snapshots taken of it.
+/// The mutex also serializes updates from saves and watcher threads.
+pub struct LocalHistory(pub Arc<Mutex<Option<PathBuf>>>);
+impl Default for LocalHistory {
+    fn default() -> Self { LocalHistory(Arc::new(Mutex::new(None))) }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Index of a file, named after its canonical key so a symlink, a relative path
+/// or a different case finds the same timeline
+fn index_file(dir: &Path, path: &str) -> PathBuf {
+    let key = hash_bytes(canonical_key(Path::new(path)).as_bytes());
+    dir.join("index").join(format!("{}.json", &key[..16]))
+}
+
+fn object_file(dir: &Path, hash: &str) -> PathBuf {
+    dir.join("objects").join(&hash[..2]).join(hash)
+}
+
+fn read_index(dir: &Path, path: &str) -> io::Result<HistoryIndex> {
+    match read_index_file(&index_file(dir, path)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HistoryIndex { path: path.to_string(), snapshots: Vec::new() }),
+        result => result,
+    }
+}
+
+fn read_index_file(file: &Path) -> io::Result<HistoryIndex> {
+    let json = fs::read_to_string(file)?;
+    serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_index(dir: &Path, index: &HistoryIndex) -> io::Result<()> {
+    let file = index_file(dir, &index.path);
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_vec(index).expect("history indexes are always serializable");
+    file_io::atomic_write(&file, &json).map_err(|e| io::Error::new(e.io_kind, e.to_string()))
+}
+
+/// Drop unlabeled snapshots beyond the configured count or age. Returns whether
+/// any were dropped; their objects are deleted by the next garbage collection.
+fn prune(index: &mut HistoryIndex, settings: &HistorySettings) -> bool {
+    let cutoff = (settings.max_age_days > 0)
+        .then(|| now_ms().saturating_sub(u64::from(settings.max_age_days) * MS_PER_DAY));
+    let mut unlabeled_kept = 0;
+    let mut removed = false;
+    // Walk newest first so the count limit keeps the most recent snapshots
+    let mut kept: Vec<Snapshot> = Vec::with_capacity(index.snapshots.len());
+    for snapshot in index.snapshots.drain(..).rev() {
+        let expired = cutoff.is_some_and(|cutoff| snapshot.timestamp_ms < cutoff);
+        if snapshot.label.is_some() || (!expired && unlabeled_kept < settings.max_snapshots) {
+            if snapshot.label.is_none() {
+                unlabeled_kept += 1;
+            }
+            kept.push(snapshot);
+        } else {
+            removed = true;
+        }
+    }
+    kept.reverse();
+    index.snapshots = kept;
+    removed
+}
+
+/// Prune every index with the current settings, then delete the objects no index
+/// refers to any more. Returns how many objects were deleted.
+fn collect_garbage(dir: &Path, settings: &HistorySettings) -> io::Result<usize> {
+    let mut referenced = HashSet::new();
+    if let Ok(read_dir) = fs::read_dir(dir.join("index")) {
+        for entry in read_dir.filter_map(|e| e.ok()) {
+            let Ok(mut index) = read_index_file(&entry.path()) else { continue };
+            if prune(&mut index, settings) {
+                let json = serde_json::to_vec(&index).expect("history indexes are always serializable");
+                file_io::atomic_write(&entry.path(), &json).map_err(|e| io::Error::new(e.io_kind, e.to_string()))?;
+            }
+            referenced.extend(index.snapshots.into_iter().map(|s| s.hash));
+        }
+    }
+    let mut deleted = 0;
+    let Ok(buckets) = fs::read_dir(dir.join("objects")) else { return Ok(0) };
+    for bucket in buckets.filter_map(|e| e.ok()) {
+        let Ok(objects) = fs::read_dir(bucket.path()) else { continue };
+        for object in objects.filter_map(|e| e.ok()) {
+            if !referenced.contains(object.file_name().to_string_lossy().as_ref()) {
+                fs::remove_file(object.path())?;
+                deleted += 1;
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+impl LocalHistory {
+    pub fn set_dir(&self, dir: PathBuf) {
+        *self.0.lock().unwrap() = Some(dir);
+    }
+
+    fn with_dir<T>(&self, f: impl FnOnce(&Path) -> io::Result<T>) -> io::Result<T> {
+        let guard = self.0.lock().unwrap();
+        let dir = guard.as_deref().ok_or_else(|| io::Error::other("Local history is not available"))?;
+        f(dir)
+    }
+
+    /// Take a snapshot of `content` for `path`, unless it matches the latest one.
+    /// Returns the new snapshot, or `None` if history is off or nothing changed.
+    pub fn record(&self, path: &str, content: &str, source: SnapshotSource, settings: &HistorySettings) -> io::Result<Option<Snapshot>> {
+        if !settings.enabled || content.len() as u64 > settings.max_file_mb * MB {
+            return Ok(None);
+        }
+        self.with_dir(|dir| {
+            let mut index = read_index(dir, path)?;
+            let hash = hash_bytes(content.as_bytes());
+            if index.snapshots.last().is_some_and(|latest| latest.hash == hash) {
+                return Ok(None);
+            }
+
+            let object = object_file(dir, &hash);
+            if !object.exists() {
+                if let Some(parent) = object.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                file_io::atomic_write(&object, content.as_bytes())
+                    .map_err(|e| io::Error::new(e.io_kind, e.to_string()))?;
+            }
+
+            let timestamp_ms = now_ms();
+            let snapshot = Snapshot {
+                id: format!("{}-{}", timestamp_ms, &hash[..8]),
+                hash,
+                timestamp_ms,
+                size: content.len() as u64,
+                source,
+                label: None,
+            };
+            index.snapshots.push(snapshot.clone());
+            prune(&mut index, settings);
+            write_index(dir, &index)?;
+            Ok(Some(snapshot))
+        })
+    }
+
+    /// Collect garbage on a background thread, so pruned snapshots are deleted
+    /// once per launch instead of on every save
+    pub fn spawn_garbage_collection(&self, settings: HistorySettings) {
+        let history = LocalHistory(self.0.clone());
+        std::thread::spawn(move || {
+            match history.with_dir(|dir| collect_garbage(dir, &settings)) {
+                Ok(deleted) => println!("Local history: deleted {} unreferenced snapshot(s)", deleted),
+                Err(e) => eprintln!("Failed to collect local history garbage: {}", e),
+            }
+        });
+    }
+
+    /// Snapshots of `path`, newest first
+    pub fn list(&self, path: &str) -> io::Result<Vec<Snapshot>> {
+        self.with_dir(|dir| {
+            let mut snapshots = read_index(dir, path)?.snapshots;
+            snapshots.reverse();
+            Ok(snapshots)
+        })
+    }
+
+    /// Text of a snapshot, or `None` if `path` has no snapshot with that id
+    pub fn content(&self, path: &str, id: &str) -> io::Result<Option<String>> {
+        self.with_dir(|dir| {
+            let index = read_index(dir, path)?;
+            let Some(snapshot) = index.snapshots.iter().find(|s| s.id == id) else { return Ok(None) };
+            let bytes = fs::read(object_file(dir, &snapshot.hash))?;
+            String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+    }
+
+    /// Name a snapshot as a checkpoint, or clear its label with `None`.
+    /// Labeled snapshots are never pruned.
+    pub fn set_label(&self, path: &str, id: &str, label: Option<String>) -> io::Result<Option<Snapshot>> {
+        self.with_dir(|dir| {
+            let mut index = read_index(dir, path)?;
+            let Some(snapshot) = index.snapshots.iter_mut().find(|s| s.id == id) else { return Ok(None) };
+            snapshot.label = label.filter(|l| !l.trim().is_empty());
+            let updated = snapshot.clone();
+            write_index(dir, &index)?;
+            Ok(Some(updated))
+        })
+    }
+}
+
+/// Unified diff from `old` to `new`, with the given names in the header
+pub fn unified_diff(old_name: &str, old: &str, new_name: &str, new: &str) -> String {
+    diffy::DiffOptions::new()
+        .set_original_filename(old_name.to_string())
+        .set_modified_filename(new_name.to_string())
+        .create_patch(old, new)
+        .to_string()
+}
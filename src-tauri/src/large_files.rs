@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::documents::Fingerprint;
+use crate::encoding::{self, TextEncoding};
+use crate::line_endings::{LineEndingCounter, LineEndingInfo};
+
+// Bytes read, decoded and sent to the frontend per `file-open-progress` event
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+const MB: u64 = 1024 * 1024;
+
+/// Thresholds for opening large files, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LargeFileSettings {
+    /// Files above this size are streamed to the editor in chunks
+    pub stream_threshold_mb: u64,
+    /// Files above this size need confirmation before they are opened
+    pub warn_threshold_mb: u64,
+    /// How much of a huge file the "first N MB" mode loads
+    pub preview_mb: u64,
+}
+
+impl Default for LargeFileSettings {
+    fn default() -> Self {
+        LargeFileSettings {
+            stream_threshold_mb: 5,
+            warn_threshold_mb: 50,
+            preview_mb: 10,
+        }
+    }
+}
+
+impl LargeFileSettings {
+    pub fn should_stream(&self, size: u64) -> bool {
+        size > self.stream_threshold_mb * MB
+    }
+
+    pub fn preview_bytes(&self) -> u64 {
+        self.preview_mb * MB
+    }
+
+    pub fn notice(&self, path: &str, size: u64, read_only: bool) -> LargeFileNotice {
+        LargeFileNotice {
+            path: path.to_string(),
+            size,
+            warn: size > self.warn_threshold_mb * MB,
+            preview_bytes: self.preview_bytes(),
+            read_only,
+        }
+    }
+}
+
+/// Sent to a tab instead of the document for a file above the stream threshold.
+/// The frontend confirms if `warn` is set, then calls `open_file_chunked`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeFileNotice {
+    pub path: String,
+    pub size: u64,
+    pub warn: bool,
+    pub preview_bytes: u64,
+    pub read_only: bool,
+}
+
+/// Payload of `file-open-progress`: the next piece of decoded text
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenProgress {
+    pub path: String,
+    pub loaded: u64,
+    pub total: u64,
+    pub chunk: String,
+}
+
+/// Everything known about a streamed file once the last chunk has been sent
+pub struct StreamedDocument {
+    pub fingerprint: Fingerprint,
+    pub encoding: TextEncoding,
+    pub line_endings: LineEndingInfo,
+    /// Only the first `limit` bytes were read
+    pub truncated: bool,
+    /// The decoded text that was sent, kept as the base for later external changes
+    pub content: String,
+}
+
+/// Read `path` in chunks of `CHUNK_SIZE`, decoding each one and handing it to
+/// `on_chunk`. Stops after `limit` bytes if given. Returns `None` if `cancel`
+/// was set before the end of the file was reached.
+pub fn stream_document(
+    path: &Path,
+    limit: Option<u64>,
+    cancel: &AtomicBool,
+    mut on_chunk: impl FnMut(OpenProgress),
+) -> io::Result<Option<StreamedDocument>> {
+    let mut file = File::open(path)?;
+    let meta = file.metadata()?;
+    let size = meta.len();
+    let total = limit.map_or(size, |limit| limit.min(size));
+    let path_str = path.to_string_lossy().to_string();
+
+    let mut hasher = Sha256::new();
+    let mut line_endings = LineEndingCounter::default();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut decoder = None;
+    let mut text_encoding = TextEncoding::default();
+    let mut loaded = 0u64;
+    let mut content = String::new();
+
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let want = (total - loaded).min(CHUNK_SIZE as u64) as usize;
+        let read = if want == 0 { 0 } else { file.read(&mut buffer[..want])? };
+        let last = read == 0;
+        let bytes = &buffer[..read];
+        hasher.update(bytes);
+
+        // The first chunk decides the encoding for the whole file
+        let mut body = bytes;
+        if decoder.is_none() {
+            let (detected, bom_len) = encoding::detect_prefix(bytes);
+            body = &bytes[bom_len.min(bytes.len())..];
+            decoder = Some(detected.encoding().new_decoder_without_bom_handling());
+            text_encoding = detected;
+        }
+        let decoder = decoder.as_mut().expect("decoder is set on the first chunk");
+        let mut chunk = String::with_capacity(decoder.max_utf8_buffer_length(body.len()).unwrap_or(body.len() * 3));
+        let _ = decoder.decode_to_string(body, &mut chunk, last);
+        line_endings.feed(&chunk);
+        content.push_str(&chunk);
+        loaded += read as u64;
+
+        if !chunk.is_empty() || last {
+            on_chunk(OpenProgress { path: path_str.clone(), loaded, total, chunk });
+        }
+        if last {
+            break;
+        }
+    }
+
+    let hash = format!("{:x}", hasher.finalize());
+    Ok(Some(StreamedDocument {
+        fingerprint: Fingerprint::from_hash(hash, loaded, Some(&meta)),
+        encoding: text_encoding,
+        line_endings: line_endings.finish(),
+        truncated: loaded < size,
+        content,
+    }))
+}
+
+// Cancellation flags of chunked opens in progress, keyed by window label.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+pub struct ChunkedOpens(pub Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>);
+impl Default for ChunkedOpens {
+    fn default() -> Self { ChunkedOpens(Arc::new(Mutex::new(HashMap::new()))) }
+}
+
+impl ChunkedOpens {
+    /// Register a new open for the window, cancelling any earlier one still running
+    pub fn start(&self, window_label: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self.0.lock().unwrap().insert(window_label.to_string(), flag.clone()) {
+            previous.store(true, Ordering::SeqCst);
+        }
+        flag
+    }
+
+    /// Ask the window's open to stop. Returns false if none was running.
+    pub fn cancel(&self, window_label: &str) -> bool {
+        match self.0.lock().unwrap().remove(window_label) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget a finished open, unless a newer one has replaced it
+    pub fn finish(&self, window_label: &str, flag: &Arc<AtomicBool>) {
+        let mut opens = self.0.lock().unwrap();
+        if opens.get(window_label).is_some_and(|current| Arc::ptr_eq(current, flag)) {
+            opens.remove(window_label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delta::ExternalContent;
+
+    #[test]
+    fn streamed_document_gets_a_delta() {
+        let dir = std::env::temp_dir().join(format!("mark-us-down-large-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("large.md");
+        let text: String = (0..60_000).map(|i| format!("Line {} of a large document\n", i)).collect();
+        std::fs::write(&path, &text).unwrap();
+
+        let mut chunks = 0;
+        let streamed = stream_document(&path, None, &AtomicBool::new(false), |_| chunks += 1).unwrap().unwrap();
+        assert!(chunks > 1);
+        assert!(!streamed.truncated);
+        assert_eq!(streamed.content, text);
+
+        let changed = format!("{}One more line\n", text);
+        match ExternalContent::between(Some(&streamed.content), changed) {
+            ExternalContent::Delta { edits } => assert_eq!(edits.len(), 1),
+            ExternalContent::Full { .. } => panic!("expected a delta"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,72 @@
 use tauri::{Manager, menu::*, Emitter, WindowEvent, WebviewUrl};
 use tauri::webview::WebviewWindowBuilder;
+use serde::Serialize;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, OnceLock};
 use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::{HashMap, HashSet};
 
+mod assets;
+mod backups;
+mod delta;
+mod documents;
+mod dropped;
+mod encoding;
+mod error;
+mod file_io;
+mod history;
+mod large_files;
+mod line_endings;
+mod merge;
+mod mounts;
+mod recovery;
+mod session;
+mod settings;
+mod tabs;
+mod views;
+mod watcher;
+
+use backups::BackupInfo;
+use delta::ExternalContent;
+use documents::{DocumentEntry, DocumentInfo, DocumentState, Fingerprint, LoadedDocument, OpenDocuments, ReadOnly};
+use error::{AppError, ErrorKind, FileErrorEvent};
+use encoding::TextEncoding;
+use file_io::{SaveError, SaveStep};
+use history::{LocalHistory, Snapshot, SnapshotSource};
+use large_files::{ChunkedOpens, LargeFileNotice};
+use line_endings::LineEnding;
+use merge::MergeOutcome;
+use recovery::{JournalDocument, JournalEntry, RecoveryJournal};
+use session::{Session, TabSession, WindowGeometry, WindowSession};
+use settings::{AppSettings, Settings};
+use tabs::{OpenFilesIn, Tabs};
+use views::{DocumentViews, OpenViews, ViewState};
+use watcher::{FileChange, Subscriber, SubscriptionKind, WatchInfo, WatchNotice, WatchSubscription, WatcherService};
+
 // Counter for generating unique window labels
 static WINDOW_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+// Counter for generating unique document ids
+static DOCUMENT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Command line flag that opens the given files read-only
+const READ_ONLY_FLAG: &str = "--readonly";
+
 // Flag to track if a window was created from a file open event (macOS)
 static FILE_OPEN_HANDLED: AtomicBool = AtomicBool::new(false);
 
+// Set while Quit walks the documents with unsaved changes, so a second Quit
+// doesn't start another walk
+static QUIT_PENDING: AtomicBool = AtomicBool::new(false);
+
+// Id prefix of the Window menu items that bring a document to the front
+const DOCUMENT_MENU_PREFIX: &str = "document:";
+
+// Buttons of the unsaved-changes prompt
+const SAVE_LABEL: &str = "Save";
+const DISCARD_LABEL: &str = "Don't Save";
+
 // macOS dock menu: app handle and menu pointer stored globally
 #[cfg(target_os = "macos")]
 static DOCK_APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
@@ -22,16 +76,48 @@ static DOCK_APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
 static DOCK_MENU_PTR: std::sync::atomic::AtomicPtr<std::ffi::c_void> =
     std::sync::atomic::AtomicPtr::new(std::ptr::null_mut());
 
-// File watcher state - keyed by (window_label, file_path) to support per-window watchers
-type FileWatchers = Arc<Mutex<HashMap<String, RecommendedWatcher>>>;
-
 // Tracks which windows are currently empty (no file, no content)
 type EmptyWindows = Arc<Mutex<HashSet<String>>>;
 
-// Pending files to open, keyed by window label — set before the window loads,
-// consumed by the frontend via the `window_ready` command once it has initialized.
-// Newtype wrapper so Tauri's state manager sees a distinct TypeId from FileWatchers.
-struct PendingFiles(Arc<Mutex<HashMap<String, (String, String)>>>);
+/// What a tab shows once its window's frontend is ready. Serialized without a tag:
+/// a document as the `(path, content, info)` array, a large file as the
+/// notice object the frontend answers with `open_file_chunked`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum PendingOpen {
+    /// The path is `None` for untitled documents restored from the recovery journal
+    Document(Option<String>, String, DocumentInfo),
+    Large(LargeFileNotice),
+}
+
+/// A tab on its way into a window, sent with `tab-opened` or returned by
+/// `window_ready`. `open` is `None` for an empty tab.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingTab {
+    id: String,
+    open: Option<PendingOpen>,
+}
+
+/// The tabs a window starts with, returned by `window_ready`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadyTabs {
+    tabs: Vec<PendingTab>,
+    active: Option<String>,
+}
+
+/// A file on its way into a window: loaded in full, or above the stream
+/// threshold, in which case the frontend streams it with `open_file_chunked`
+enum FileToOpen {
+    Loaded(String, LoadedDocument),
+    Large(LargeFileNotice),
+}
+
+// Tabs waiting for their window's frontend, keyed by window label — set before the
+// window loads, consumed by the frontend via the `window_ready` command once it has initialized.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+struct PendingFiles(Arc<Mutex<HashMap<String, Vec<PendingTab>>>>);
 impl Default for PendingFiles {
     fn default() -> Self { PendingFiles(Arc::new(Mutex::new(HashMap::new()))) }
 }
@@ -43,18 +129,138 @@ impl Default for ReadyWindows {
     fn default() -> Self { ReadyWindows(Arc::new(Mutex::new(HashSet::new()))) }
 }
 
+// Documents with unsaved changes, by document id, as reported by their frontends.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+struct DirtyDocuments(Arc<Mutex<HashSet<String>>>);
+impl Default for DirtyDocuments {
+    fn default() -> Self { DirtyDocuments(Arc::new(Mutex::new(HashSet::new()))) }
+}
+
+/// How the user answered the unsaved-changes prompt
+enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// What was going on when the user was asked about a document's unsaved changes,
+/// and so what happens once they are saved or discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseAction {
+    /// Its tab is closing; the tab closes
+    Tab,
+    /// Its window is closing; the window asks about its next document, or closes
+    Window,
+    /// The app is quitting; the quit moves on to the next document
+    Quit,
+}
+
+// Documents whose unsaved-changes prompt was answered with Save, waiting for the
+// frontend to report back through `finish_save_before_close`.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+struct PendingCloses(Arc<Mutex<HashMap<String, CloseAction>>>);
+impl Default for PendingCloses {
+    fn default() -> Self { PendingCloses(Arc::new(Mutex::new(HashMap::new()))) }
+}
+
+// The Window menu's document items, kept so they can be updated in place rather
+// than rebuilding the menu bar. Only touched on the main thread.
+struct WindowMenu(Arc<Mutex<Option<WindowMenuItems>>>);
+impl Default for WindowMenu {
+    fn default() -> Self { WindowMenu(Arc::new(Mutex::new(None))) }
+}
+
+struct WindowMenuItems {
+    submenu: Submenu<tauri::Wry>,
+    separator: Option<PredefinedMenuItem<tauri::Wry>>,
+    documents: Vec<(String, CheckMenuItem<tauri::Wry>)>,
+    /// Window last brought to the front, whose front document gets the checkmark
+    focused: Option<String>,
+}
+
 /// Generate a unique window label
 fn generate_window_label() -> String {
     let count = WINDOW_COUNTER.fetch_add(1, Ordering::SeqCst);
     format!("doc-{}", count)
 }
 
-/// Create a new document window, optionally with a file to open
-fn create_document_window(
-    app_handle: &tauri::AppHandle,
-    file_path: Option<String>,
-    content: Option<String>,
-) -> Result<tauri::WebviewWindow, String> {
+/// Generate a unique document id
+fn generate_document_id() -> String {
+    let count = DOCUMENT_COUNTER.fetch_add(1, Ordering::SeqCst);
+    format!("tab-{}", count)
+}
+
+/// Remember the disk state of a document that was loaded or saved,
+/// so later saves can detect changes made by someone else.
+fn record_document(app_handle: &tauri::AppHandle, document: &str, state: DocumentState) {
+    let open_documents: tauri::State<OpenDocuments> = app_handle.state::<OpenDocuments>();
+    let renamed = open_documents.0.lock().unwrap().get(document).map_or(true, |doc| doc.path != state.path);
+    open_documents.record(document, state);
+    if renamed {
+        refresh_window_menu(app_handle);
+    }
+}
+
+/// File name a document is shown by in titles, menus and prompts
+fn document_name(path: Option<&str>) -> String {
+    match path {
+        Some(path) => std::path::Path::new(path).file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string()),
+        None => "Untitled".to_string(),
+    }
+}
+
+/// Report a failure that has no command result to travel back in (dialog callbacks,
+/// drag and drop) to the window as a `file-error` event, and log it.
+fn emit_file_error<E: Emitter<tauri::Wry>>(emitter: &E, window_label: &str, operation: &'static str, error: AppError) {
+    eprintln!("Error during {}: {}", operation, error);
+    let _ = emitter.emit_to(window_label, "file-error", FileErrorEvent { operation, error });
+}
+
+/// Location of the settings file, or `None` if the platform has no config directory
+fn settings_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_config_dir().ok().map(|dir| dir.join(settings::SETTINGS_FILE))
+}
+
+/// Location of the crash-recovery journal, or `None` if the platform has no data directory
+fn recovery_dir(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|dir| dir.join(recovery::RECOVERY_DIR))
+}
+
+/// Location of the saved session, or `None` if the platform has no data directory
+fn session_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|dir| dir.join(session::SESSION_FILE))
+}
+
+/// Location of the per-document view state store, or `None` if the platform has no data directory
+fn view_state_path(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|dir| dir.join(views::VIEW_STATE_FILE))
+}
+
+/// Location of central backups, or `None` if the platform has no data directory
+fn backups_dir(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|dir| dir.join(backups::BACKUPS_DIR))
+}
+
+/// Location of the local history store, or `None` if the platform has no data directory
+fn history_dir(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_data_dir().ok().map(|dir| dir.join(history::HISTORY_DIR))
+}
+
+/// Add a snapshot of `content` to the local history of `path`. History is a
+/// background convenience, so failures are only logged.
+fn record_history(app_handle: &tauri::AppHandle, path: &str, content: &str, source: SnapshotSource) {
+    let settings = app_handle.state::<AppSettings>().get().history;
+    match app_handle.state::<LocalHistory>().record(path, content, source, &settings) {
+        Ok(Some(snapshot)) => println!("Recorded history snapshot {} for {}", snapshot.id, path),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to record history for {}: {}", path, e),
+    }
+}
+
+/// Build a document window with a fresh label
+fn build_document_window(app_handle: &tauri::AppHandle) -> Result<(String, tauri::WebviewWindow), AppError> {
     let label = generate_window_label();
     println!("Creating new document window with label: {}", label);
 
@@ -65,47 +271,565 @@ fn create_document_window(
         .resizable(true)
         .center()
         .build()
-        .map_err(|e| format!("Failed to create window: {}", e))?;
+        .map_err(|e| AppError::new(ErrorKind::WindowFailed, format!("Failed to create window: {}", e)))?;
+    Ok((label, window))
+}
+
+/// Load `path` to open it, unless it is large enough to be streamed instead
+fn prepare_file_open(app_handle: &tauri::AppHandle, path: &std::path::Path, read_only: bool) -> std::io::Result<FileToOpen> {
+    let path_str = path.to_string_lossy().to_string();
+    let size = fs::metadata(path)?.len();
+    let settings = app_handle.state::<AppSettings>().get().large_files;
+    if settings.should_stream(size) {
+        println!("{} is {} bytes; it will be streamed", path_str, size);
+        return Ok(FileToOpen::Large(settings.notice(&path_str, size, read_only)));
+    }
+    let loaded = documents::load_document(path)?.with_read_only(read_only);
+    Ok(FileToOpen::Loaded(path_str, loaded))
+}
+
+/// Record a file as a document and turn it into what the frontend receives
+fn pending_open(app_handle: &tauri::AppHandle, document: &str, file: FileToOpen) -> PendingOpen {
+    match file {
+        FileToOpen::Loaded(path, loaded) => {
+            record_document(app_handle, document, DocumentState::loaded(&path, &loaded));
+            let mut info = loaded.info();
+            info.view = app_handle.state::<DocumentViews>().get(std::path::Path::new(&path));
+            PendingOpen::Document(Some(path), loaded.content, info)
+        }
+        FileToOpen::Large(notice) => PendingOpen::Large(notice),
+    }
+}
+
+/// Hand a tab to a window as `tab-opened` if its frontend is ready, or queue it for
+/// `window_ready` if not. A tab the window already has shows the new content instead.
+fn deliver_tab(app_handle: &tauri::AppHandle, window_label: &str, tab: PendingTab) {
+    refresh_window_menu(app_handle);
+    let is_ready = app_handle.state::<ReadyWindows>().0.lock().unwrap().contains(window_label);
+    if is_ready {
+        if let Err(e) = app_handle.emit_to(window_label, "tab-opened", &tab) {
+            eprintln!("Failed to send tab {} to window {}: {}", tab.id, window_label, e);
+        }
+        return;
+    }
+    let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
+    let mut pending = pending_files.inner().0.lock().unwrap();
+    let queued = pending.entry(window_label.to_string()).or_default();
+    match queued.iter_mut().find(|queued| queued.id == tab.id) {
+        Some(existing) => *existing = tab,
+        None => queued.push(tab),
+    }
+    println!("Window {} not ready yet; stored as pending tab", window_label);
+}
+
+/// Open a file, or an empty document, in a new tab of the window, right after the
+/// tab in front. Returns the new document id.
+fn open_tab(app_handle: &tauri::AppHandle, window_label: &str, file: Option<FileToOpen>) -> String {
+    let document = generate_document_id();
+    app_handle.state::<Tabs>().open(window_label, &document);
+    let open = file.map(|file| pending_open(app_handle, &document, file));
+    deliver_tab(app_handle, window_label, PendingTab { id: document.clone(), open });
+    println!("Opened tab {} in window {}", document, window_label);
+    document
+}
+
+/// Create a new document window with a tab for each file, the one at `active` in
+/// front, or with a single empty tab if there are none. Returns the window and the
+/// ids of its documents in tab order.
+fn create_window_with_tabs(
+    app_handle: &tauri::AppHandle,
+    files: Vec<FileToOpen>,
+    active: usize,
+) -> Result<(tauri::WebviewWindow, Vec<String>), AppError> {
+    let (label, window) = build_document_window(app_handle)?;
+    let tabs = app_handle.state::<Tabs>();
 
     // If no file is being opened, register this window as empty
-    if file_path.is_none() {
+    let mut pending = Vec::new();
+    if files.is_empty() {
+        let document = generate_document_id();
+        tabs.open(&label, &document);
+        pending.push(PendingTab { id: document, open: None });
         let empty_windows: tauri::State<EmptyWindows> = app_handle.state::<EmptyWindows>();
         let mut empty_set = empty_windows.inner().lock().unwrap();
         empty_set.insert(label.clone());
         println!("Registered window {} as empty", label);
     }
+    for file in files {
+        let document = generate_document_id();
+        tabs.open(&label, &document);
+        let open = pending_open(app_handle, &document, file);
+        pending.push(PendingTab { id: document, open: Some(open) });
+    }
+    let documents: Vec<String> = pending.iter().map(|tab| tab.id.clone()).collect();
+    if let Some(document) = documents.get(active) {
+        tabs.activate(document);
+        // The frontend keeps the title up to date once it has loaded
+        let path = match &pending[active].open {
+            Some(PendingOpen::Document(path, ..)) => path.clone(),
+            Some(PendingOpen::Large(notice)) => Some(notice.path.clone()),
+            None => None,
+        };
+        let _ = window.set_title(&format!("{} - Mark-us-Down", document_name(path.as_deref())));
+    }
+    refresh_window_menu(app_handle);
+
+    // Store the tabs as pending. The frontend will retrieve them via the `window_ready`
+    // command once it has finished initializing. This avoids a race where a
+    // fixed-delay emit fires before the listener is set up.
+    let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
+    pending_files.inner().0.lock().unwrap().insert(label.clone(), pending);
+    println!("Stored {} pending tab(s) for window {}", documents.len(), label);
+
+    Ok((window, documents))
+}
+
+/// Create a new document window, optionally with a file to open
+fn create_document_window(
+    app_handle: &tauri::AppHandle,
+    document: Option<FileToOpen>,
+) -> Result<tauri::WebviewWindow, AppError> {
+    create_window_with_tabs(app_handle, document.into_iter().collect(), 0).map(|(window, _)| window)
+}
 
-    // If we have a file to open, store it as a pending file. The frontend will
-    // retrieve it via the `window_ready` command once it has finished initializing.
-    // This avoids a race where a fixed-delay emit fires before the listener is set up.
-    if let (Some(path), Some(file_content)) = (file_path, content) {
-        let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
-        let mut pending = pending_files.inner().0.lock().unwrap();
-        pending.insert(label.clone(), (path.clone(), file_content));
-        println!("Stored pending file for window {}: {}", label, path);
+/// Open a buffer recovered from the journal in a new window. The buffer stays
+/// unsaved; if it belonged to a file, the disk state it was based on is restored
+/// so saving it still detects changes made in the meantime.
+fn restore_recovered_document(app_handle: &tauri::AppHandle, entry: JournalEntry) -> Result<tauri::WebviewWindow, AppError> {
+    let (label, window) = build_document_window(app_handle)?;
+    let document_id = generate_document_id();
+    app_handle.state::<Tabs>().open(&label, &document_id);
+
+    let mut info = DocumentInfo {
+        encoding: TextEncoding::default(),
+        line_endings: line_endings::detect(&entry.content),
+        recovered: true,
+        unsaved: true,
+        read_only: None,
+        view: None,
+    };
+    if let (Some(path), Some(document)) = (&entry.path, entry.document) {
+        info.encoding = document.encoding.clone();
+        record_document(app_handle, &document_id, DocumentState {
+            path: path.clone(),
+            fingerprint: document.fingerprint,
+            encoding: document.encoding,
+            line_ending: document.line_ending,
+            read_only: None,
+            base: None,
+        });
     }
 
+    // Journal the buffer again under this session, so it survives another crash
+    // before the user saves it
+    let journal = app_handle.state::<RecoveryJournal>();
+    let document = app_handle.state::<OpenDocuments>().get(&document_id).map(|doc| JournalDocument {
+        fingerprint: doc.fingerprint,
+        encoding: doc.encoding,
+        line_ending: doc.line_ending,
+    });
+    journal.update(&document_id, entry.path.clone(), entry.content.clone(), document);
+    journal.remove_entry(&entry.id);
+
+    println!("Restoring recovered document {} into window {}", entry.path.as_deref().unwrap_or("(untitled)"), label);
+    let open = PendingOpen::Document(entry.path, entry.content, info);
+    let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
+    pending_files.inner().0.lock().unwrap().insert(label, vec![PendingTab { id: document_id, open: Some(open) }]);
+    refresh_window_menu(app_handle);
     Ok(window)
 }
 
+/// Offer to restore buffers left in the journal by a session that didn't exit
+/// cleanly. Declining deletes them. `on_done` runs after the user has answered.
+fn offer_recovery(app_handle: &tauri::AppHandle, entries: Vec<JournalEntry>, on_done: impl FnOnce(&tauri::AppHandle) + Send + 'static) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let names: Vec<String> = entries.iter().map(|entry| document_name(entry.path.as_deref())).collect();
+    let message = format!(
+        "Mark-us-Down did not shut down properly. Unsaved changes were recovered for:\n\n{}\n\nRestore them?",
+        names.join("\n"),
+    );
+
+    let app = app_handle.clone();
+    app_handle.dialog()
+        .message(message)
+        .title("Recover Unsaved Documents")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("Restore".to_string(), "Discard".to_string()))
+        .show(move |restore| {
+            let journal = app.state::<RecoveryJournal>();
+            for entry in entries {
+                if restore {
+                    if let Err(e) = restore_recovered_document(&app, entry) {
+                        eprintln!("Failed to restore recovered document: {}", e);
+                    }
+                } else {
+                    journal.remove_entry(&entry.id);
+                }
+            }
+            on_done(&app);
+        });
+}
+
+/// Position of a window in the order windows were opened, from its `doc-N` label
+fn window_number(label: &str) -> u64 {
+    label.strip_prefix("doc-").and_then(|n| n.parse().ok()).unwrap_or(u64::MAX)
+}
+
+/// Every open document, window by window in the order they were opened and tab by
+/// tab within each, from what the backend has recorded about them
+fn document_entries(app_handle: &tauri::AppHandle) -> Vec<DocumentEntry> {
+    let tabs = app_handle.state::<Tabs>();
+    let open_documents = app_handle.state::<OpenDocuments>();
+    let dirty = app_handle.state::<DirtyDocuments>().0.lock().unwrap().clone();
+    let mut labels: Vec<String> = app_handle.webview_windows().into_keys().collect();
+    labels.sort_by_key(|label| window_number(label));
+    let mut entries = Vec::new();
+    for label in labels {
+        let list = tabs.list(&label);
+        for document in &list.tabs {
+            let active = list.active.as_ref() == Some(document);
+            let state = open_documents.get(document);
+            entries.push(DocumentEntry::new(document, &label, active, state, dirty.contains(document)));
+        }
+    }
+    entries
+}
+
+/// What the session file records about one open window. Untitled tabs are left
+/// out; a window with nothing else is recorded with one empty tab.
+fn window_session(app_handle: &tauri::AppHandle, window: &tauri::WebviewWindow) -> WindowSession {
+    let list = app_handle.state::<Tabs>().list(window.label());
+    let open_documents = app_handle.state::<OpenDocuments>();
+    let open_views = app_handle.state::<OpenViews>();
+    let mut tabs = Vec::new();
+    let mut active = 0;
+    for document in &list.tabs {
+        let Some(doc) = open_documents.get(document) else { continue };
+        if list.active.as_ref() == Some(document) {
+            active = tabs.len();
+        }
+        tabs.push(TabSession {
+            path: Some(doc.path),
+            read_only: doc.read_only == Some(ReadOnly::Requested),
+            view: open_views.get(document).unwrap_or_default(),
+        });
+    }
+    if tabs.is_empty() {
+        let view = list.active.and_then(|document| open_views.get(&document)).unwrap_or_default();
+        tabs.push(TabSession { path: None, read_only: false, view });
+    }
+    let geometry = match (window.outer_position(), window.inner_size()) {
+        (Ok(position), Ok(size)) => Some(WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized: window.is_maximized().unwrap_or(false),
+            fullscreen: window.is_fullscreen().unwrap_or(false),
+        }),
+        _ => None,
+    };
+    WindowSession {
+        tabs,
+        active,
+        geometry,
+        monitor: window.current_monitor().ok().flatten().and_then(|monitor| monitor.name().cloned()),
+    }
+}
+
+/// Remember how a document is shown, so reopening the file later brings back the same view
+fn remember_document_view(app_handle: &tauri::AppHandle, document: &str) {
+    let state = app_handle.state::<OpenDocuments>().get(document);
+    let view = app_handle.state::<OpenViews>().get(document);
+    if let (Some(state), Some(view)) = (state, view) {
+        let settings = app_handle.state::<AppSettings>().get().view_state;
+        app_handle.state::<DocumentViews>().remember(std::path::Path::new(&state.path), view, &settings);
+    }
+}
+
+/// Forget what is kept about a document as its tab goes away. Closing discards
+/// any unsaved changes, so they are dropped from the recovery journal too.
+fn forget_document(app_handle: &tauri::AppHandle, document: &str) {
+    remember_document_view(app_handle, document);
+    app_handle.state::<OpenDocuments>().remove(document);
+    app_handle.state::<RecoveryJournal>().discard(document);
+    app_handle.state::<OpenViews>().remove(document);
+    app_handle.state::<DirtyDocuments>().0.lock().unwrap().remove(document);
+    app_handle.state::<PendingCloses>().0.lock().unwrap().remove(document);
+}
+
+/// Bring a document's tab to the front of its window, and the window to the front
+/// of the screen. Returns the window, or `None` if the document isn't open.
+fn show_tab(app_handle: &tauri::AppHandle, document: &str) -> Option<tauri::WebviewWindow> {
+    let label = app_handle.state::<Tabs>().activate(document)?;
+    let window = app_handle.get_webview_window(&label)?;
+    let _ = window.emit_to(&label, "tab-activate", document);
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    update_window_menu(app_handle);
+    Some(window)
+}
+
+/// Close a document's tab, asking about unsaved changes first
+fn request_close_tab(app_handle: &tauri::AppHandle, document: &str) {
+    let is_dirty = app_handle.state::<DirtyDocuments>().0.lock().unwrap().contains(document);
+    if is_dirty {
+        confirm_unsaved_changes(app_handle, document, CloseAction::Tab);
+    } else {
+        close_document(app_handle, document);
+    }
+}
+
+/// Close a document's tab without asking. The tab next to it comes to the front;
+/// closing the last tab closes the window.
+fn close_document(app_handle: &tauri::AppHandle, document: &str) {
+    let tabs = app_handle.state::<Tabs>();
+    let Some(window_label) = tabs.window_of(document) else { return };
+    if tabs.list(&window_label).tabs.len() <= 1 {
+        if let Some(window) = app_handle.get_webview_window(&window_label) {
+            let _ = window.close();
+        }
+        return;
+    }
+    if let Some(doc) = app_handle.state::<OpenDocuments>().get(document) {
+        app_handle.state::<WatcherService>().unsubscribe(&window_label, &doc.path);
+    }
+    forget_document(app_handle, document);
+    tabs.close(document);
+    println!("Closed tab {} in window {}", document, window_label);
+    let _ = app_handle.emit_to(window_label.as_str(), "tab-closed", document);
+    if let Some(active) = tabs.active(&window_label) {
+        let _ = app_handle.emit_to(window_label.as_str(), "tab-activate", active);
+    }
+    refresh_window_menu(app_handle);
+}
+
+/// Ask whether to save a document's unsaved changes before its tab, its window or
+/// the app goes away. The tab is brought to the front first so the user can see
+/// which document the question is about.
+fn confirm_unsaved_changes(app_handle: &tauri::AppHandle, document: &str, action: CloseAction) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult};
+
+    let Some(window) = show_tab(app_handle, document) else { return };
+    let path = app_handle.state::<OpenDocuments>().get(document).map(|doc| doc.path);
+    let name = document_name(path.as_deref());
+
+    let app = app_handle.clone();
+    let document = document.to_string();
+    app_handle.dialog()
+        .message(format!("Do you want to save the changes you made to {}?\n\nYour changes will be lost if you don't save them.", name))
+        .title("Unsaved Changes")
+        .kind(MessageDialogKind::Warning)
+        .parent(&window)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            SAVE_LABEL.to_string(),
+            DISCARD_LABEL.to_string(),
+            "Cancel".to_string(),
+        ))
+        .show_with_result(move |result| {
+            let choice = match result {
+                MessageDialogResult::Yes => CloseChoice::Save,
+                MessageDialogResult::No => CloseChoice::Discard,
+                MessageDialogResult::Custom(label) if label == SAVE_LABEL => CloseChoice::Save,
+                MessageDialogResult::Custom(label) if label == DISCARD_LABEL => CloseChoice::Discard,
+                _ => CloseChoice::Cancel,
+            };
+            resolve_unsaved_changes(&app, &document, action, choice);
+        });
+}
+
+/// Act on the answer to the unsaved-changes prompt for a document. Saving is left
+/// to the frontend, which holds the buffer and may need a Save As dialog; it
+/// reports back through `finish_save_before_close`.
+fn resolve_unsaved_changes(app_handle: &tauri::AppHandle, document: &str, action: CloseAction, choice: CloseChoice) {
+    match choice {
+        CloseChoice::Save => {
+            app_handle.state::<PendingCloses>().0.lock().unwrap().insert(document.to_string(), action);
+            if let Some(window_label) = app_handle.state::<Tabs>().window_of(document) {
+                let _ = app_handle.emit_to(window_label.as_str(), "save-before-close", document);
+            }
+        }
+        CloseChoice::Discard => {
+            app_handle.state::<DirtyDocuments>().0.lock().unwrap().remove(document);
+            app_handle.state::<RecoveryJournal>().discard(document);
+            continue_close(app_handle, document, action);
+        }
+        CloseChoice::Cancel => {
+            if action == CloseAction::Quit && QUIT_PENDING.swap(false, Ordering::SeqCst) {
+                println!("Quit cancelled at document {}", document);
+            }
+        }
+    }
+}
+
+/// Go on with what the unsaved-changes prompt interrupted, now that the document's
+/// changes have been saved or discarded
+fn continue_close(app_handle: &tauri::AppHandle, document: &str, action: CloseAction) {
+    match action {
+        CloseAction::Tab => close_document(app_handle, document),
+        // Closing again asks about the window's next document with unsaved changes
+        CloseAction::Window => {
+            let window = app_handle.state::<Tabs>().window_of(document)
+                .and_then(|label| app_handle.get_webview_window(&label));
+            if let Some(window) = window {
+                let _ = window.close();
+            }
+        }
+        CloseAction::Quit => continue_quit(app_handle),
+    }
+}
+
+/// Quit once every document with unsaved changes has been saved or discarded,
+/// asking about them one at a time, window by window in the order they were
+/// opened and tab by tab within each. Cancelling any of the prompts cancels the quit.
+/// Whether any open tab holds unsaved changes. Ids the frontend reported dirty
+/// after their tab was forgotten don't count.
+fn has_unsaved_documents(app_handle: &tauri::AppHandle) -> bool {
+    document_entries(app_handle).iter().any(|document| document.dirty)
+}
+
+fn continue_quit(app_handle: &tauri::AppHandle) {
+    let documents = document_entries(app_handle);
+    match documents.iter().find(|document| document.dirty) {
+        Some(document) => confirm_unsaved_changes(app_handle, &document.id, CloseAction::Quit),
+        None => {
+            QUIT_PENDING.store(false, Ordering::SeqCst);
+            save_session(app_handle, None);
+            for document in &documents {
+                remember_document_view(app_handle, &document.id);
+            }
+            app_handle.exit(0);
+        }
+    }
+}
+
+/// Write the open windows to the session file, leaving out `closing`, a window
+/// that is about to close. Does nothing when session restore is turned off.
+fn save_session(app_handle: &tauri::AppHandle, closing: Option<&str>) {
+    if !app_handle.state::<AppSettings>().get().restore_session {
+        return;
+    }
+    let Some(path) = session_path(app_handle) else { return };
+    let mut windows: Vec<tauri::WebviewWindow> = app_handle.webview_windows().into_values()
+        .filter(|window| Some(window.label()) != closing)
+        .collect();
+    windows.sort_by_key(|window| window_number(window.label()));
+    let session = Session {
+        windows: windows.iter().map(|window| window_session(app_handle, window)).collect(),
+    };
+    match session.save(&path) {
+        Ok(()) => println!("Saved session with {} window(s)", session.windows.len()),
+        Err(e) => eprintln!("Failed to save session: {}", e),
+    }
+}
+
+/// Reopen the windows of the last session. Returns how many were restored, so
+/// the caller can fall back to an empty window.
+fn restore_session(app_handle: &tauri::AppHandle) -> usize {
+    if !app_handle.state::<AppSettings>().get().restore_session {
+        return 0;
+    }
+    let Some(path) = session_path(app_handle) else { return 0 };
+    let session = Session::load(&path);
+    let mut restored = 0;
+    for saved in session.windows {
+        match restore_session_window(app_handle, saved) {
+            Ok(_) => restored += 1,
+            Err(e) => eprintln!("Failed to restore window from session: {}", e),
+        }
+    }
+    println!("Restored {} window(s) from the last session", restored);
+    restored
+}
+
+/// Open one window of a saved session. Tabs whose file can't be opened any more are
+/// left out, and a window left without any isn't restored. The view state of each
+/// tab waits in `OpenViews` until the frontend asks for it with `get_view_state`.
+fn restore_session_window(app_handle: &tauri::AppHandle, saved: WindowSession) -> Result<tauri::WebviewWindow, AppError> {
+    let mut files = Vec::new();
+    let mut views = Vec::new();
+    let mut active = 0;
+    let mut failure = None;
+    for (index, tab) in saved.tabs.iter().enumerate() {
+        let Some(path) = &tab.path else { continue };
+        match prepare_file_open(app_handle, std::path::Path::new(path), tab.read_only) {
+            Ok(file) => {
+                if index == saved.active {
+                    active = files.len();
+                }
+                files.push(file);
+                views.push(tab.view);
+            }
+            Err(e) => {
+                eprintln!("Failed to restore {} from session: {}", path, e);
+                failure = Some(AppError::io(e, path));
+            }
+        }
+    }
+    if let (true, Some(e)) = (files.is_empty(), failure) {
+        return Err(e);
+    }
+    // A window without files comes back with the view of its empty tab
+    if files.is_empty() {
+        views = saved.tabs.first().map(|tab| tab.view).into_iter().collect();
+    }
+    let (window, documents) = create_window_with_tabs(app_handle, files, active)?;
+    if let Some(geometry) = saved.geometry {
+        place_window(&window, &geometry, saved.monitor.as_deref());
+    }
+    let open_views = app_handle.state::<OpenViews>();
+    for (document, view) in documents.iter().zip(views) {
+        open_views.set(document, view);
+    }
+    Ok(window)
+}
+
+/// Give a restored window its saved size, and its saved position if the monitor it
+/// was on is still connected; otherwise it stays centred on the current one.
+fn place_window(window: &tauri::WebviewWindow, geometry: &WindowGeometry, monitor: Option<&str>) {
+    let _ = window.set_size(tauri::PhysicalSize::new(geometry.width, geometry.height));
+    let on_saved_monitor = window.available_monitors().unwrap_or_default().iter().any(|candidate| {
+        let (origin, size) = (candidate.position(), candidate.size());
+        candidate.name().map(String::as_str) == monitor
+            && (origin.x..origin.x + size.width as i32).contains(&geometry.x)
+            && (origin.y..origin.y + size.height as i32).contains(&geometry.y)
+    });
+    if on_saved_monitor {
+        let _ = window.set_position(tauri::PhysicalPosition::new(geometry.x, geometry.y));
+    }
+    if geometry.maximized {
+        let _ = window.maximize();
+    }
+    if geometry.fullscreen {
+        let _ = window.set_fullscreen(true);
+    }
+}
+
 // Tauri commands for file operations
 
 #[tauri::command]
-async fn new_file(window: tauri::Window) -> Result<(), String> {
+async fn new_file(window: tauri::Window) -> Result<(), AppError> {
     // Reset the file state in the current window
-    window.emit_to(window.label(), "file-new", ()).map_err(|e| e.to_string())?;
+    window.emit_to(window.label(), "file-new", ())?;
     Ok(())
 }
 
 #[tauri::command]
-async fn create_new_window(app_handle: tauri::AppHandle) -> Result<(), String> {
+async fn create_new_window(app_handle: tauri::AppHandle) -> Result<(), AppError> {
     // Create a new empty document window
-    create_document_window(&app_handle, None, None)?;
+    create_document_window(&app_handle, None)?;
     Ok(())
 }
 
 #[tauri::command]
-async fn save_file_dialog(window: tauri::Window, app_handle: tauri::AppHandle, content: String) -> Result<Option<String>, String> {
+async fn save_file_dialog(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    content: String,
+) -> Result<Option<String>, AppError> {
     use tauri_plugin_dialog::DialogExt;
 
     let dialog = app_handle.dialog().file()
@@ -115,16 +839,23 @@ async fn save_file_dialog(window: tauri::Window, app_handle: tauri::AppHandle, c
 
     let window_clone = window.clone();
     let window_label = window.label().to_string();
+    // Save As keeps the encoding and line endings of the document being saved
+    let known = app_handle.state::<OpenDocuments>().get(&document);
+    let text_encoding = known.as_ref().map(|doc| doc.encoding.clone()).unwrap_or_default();
+    let document_ending = known.map(|doc| doc.line_ending)
+        .unwrap_or_else(|| line_endings::detect(&content).dominant);
+    let line_ending = app_handle.state::<AppSettings>().get().line_ending_policy.resolve(document_ending);
     dialog.save_file(move |path| {
         if let Some(path) = path {
             let path_str = path.to_string();
             let path_buf = PathBuf::from(&path_str);
-            match fs::write(&path_buf, &content) {
+            let saved_path = path_buf.to_string_lossy().to_string();
+            match write_document(&app_handle, &document, &saved_path, &content, text_encoding, line_ending) {
                 Ok(_) => {
-                    let _ = window_clone.emit_to(&window_label, "file-saved", path_buf.to_string_lossy().to_string());
+                    let _ = window_clone.emit_to(&window_label, "file-saved", (document, saved_path));
                 }
                 Err(e) => {
-                    eprintln!("Error saving file: {}", e);
+                    emit_file_error(&window_clone, &window_label, "save", e.into());
                 }
             }
         }
@@ -133,92 +864,665 @@ async fn save_file_dialog(window: tauri::Window, app_handle: tauri::AppHandle, c
     Ok(None)
 }
 
+/// Normalize line endings, encode and atomically write a document, then record
+/// its new disk state
+fn write_document(
+    app_handle: &tauri::AppHandle,
+    document: &str,
+    path: &str,
+    content: &str,
+    text_encoding: TextEncoding,
+    line_ending: LineEnding,
+) -> Result<(), SaveError> {
+    let target = std::path::Path::new(path);
+    let content = line_endings::normalize(content, line_ending);
+    let bytes = encoding::encode(&content, &text_encoding)
+        .map_err(|e| SaveError::new(target, SaveStep::Encode, e))?;
+    // Keep the version about to be replaced. A failed backup is reported but
+    // doesn't stop the save, so an unwritable backup folder never loses work.
+    let backup_settings = app_handle.state::<AppSettings>().get().backups;
+    match backups::create_backup(target, &backup_settings, backups_dir(app_handle).as_deref()) {
+        Ok(Some(backup)) => println!("Backed up {} to {}", path, backup.display()),
+        Ok(None) => {}
+        Err(e) => {
+            let window_label = app_handle.state::<Tabs>().window_of(document).unwrap_or_default();
+            emit_file_error(app_handle, &window_label, "backup", AppError::io(e, target));
+        }
+    }
+    file_io::atomic_write(target, &bytes)?;
+    record_history(app_handle, path, &content, SnapshotSource::Save);
+    let fingerprint = Fingerprint::from_bytes(&bytes, fs::metadata(target).ok().as_ref());
+    record_document(app_handle, document, DocumentState {
+        path: path.to_string(),
+        fingerprint,
+        encoding: text_encoding,
+        line_ending,
+        read_only: None,
+        base: Some(Arc::from(content.as_str())),
+    });
+    // The buffer is on disk now, so it no longer needs recovering
+    app_handle.state::<RecoveryJournal>().discard(document);
+    Ok(())
+}
+
+/// Shared implementation of `save_file` and `save_with_encoding`. Unless `overwrite`
+/// is set, refuses with a conflict if the file changed on disk since it was loaded.
+fn save_document(
+    window: &tauri::Window,
+    app_handle: &tauri::AppHandle,
+    document: String,
+    path: String,
+    content: String,
+    encoding_override: Option<TextEncoding>,
+    overwrite: bool,
+) -> Result<(), AppError> {
+    let target = std::path::Path::new(&path);
+    let known = app_handle.state::<OpenDocuments>().get(&document)
+        .filter(|doc| doc.path == path);
+
+    // Read-only documents can only be saved elsewhere. Permissions are checked
+    // again in case they were fixed since the document was opened.
+    if let Some(reason) = known.as_ref().and_then(|doc| doc.read_only) {
+        if reason == ReadOnly::Requested || !documents::is_writable(target) {
+            return Err(AppError::read_only(reason, target));
+        }
+    }
+
+    if let (false, Some(doc)) = (overwrite, &known) {
+        let conflict = documents::check_for_conflict(target, &doc.fingerprint)
+            .map_err(|e| SaveError::new(target, SaveStep::CheckConflict, e))?;
+        if let Some(conflict) = conflict {
+            println!("Refusing to save {}: file changed on disk", path);
+            return Err(AppError::conflict(conflict));
+        }
+    }
+
+    // Keep the encoding the document was opened with unless the user picked another
+    let text_encoding = encoding_override
+        .or_else(|| known.as_ref().map(|doc| doc.encoding.clone()))
+        .unwrap_or_default();
+    // Line endings follow the configured policy; "preserve" keeps the document's own style
+    let document_ending = known.map(|doc| doc.line_ending)
+        .unwrap_or_else(|| line_endings::detect(&content).dominant);
+    let line_ending = app_handle.state::<AppSettings>().get().line_ending_policy.resolve(document_ending);
+    write_document(app_handle, &document, &path, &content, text_encoding, line_ending)?;
+    let _ = window.emit_to(window.label(), "file-saved", (document, path));
+    Ok(())
+}
+
+/// Save a document. If the file changed on disk since the document was loaded or
+/// last saved, the save is refused with a `conflict` error carrying the disk
+/// version; pass `overwrite: true` to save anyway once the user has decided.
+#[tauri::command]
+async fn save_file(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    content: String,
+    overwrite: Option<bool>,
+) -> Result<(), AppError> {
+    save_document(&window, &app_handle, document, path, content, None, overwrite.unwrap_or(false))
+}
+
+/// "Save with Encoding…": save the document converted to another character encoding,
+/// which is then kept for subsequent saves.
+#[tauri::command]
+async fn save_with_encoding(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    content: String,
+    encoding: String,
+    bom: Option<bool>,
+    overwrite: Option<bool>,
+) -> Result<(), AppError> {
+    let text_encoding = TextEncoding::from_label(&encoding, bom.unwrap_or(false))
+        .ok_or_else(|| AppError::invalid_argument(format!("Unknown encoding: {}", encoding)))?;
+    save_document(&window, &app_handle, document, path, content, Some(text_encoding), overwrite.unwrap_or(false))
+}
+
+/// Open `path` as `document`, or in the window's tab in front if none is given. With
+/// `read_only: true` the document is locked for reference; it is also locked when the
+/// file isn't writable. Files above the stream threshold are refused with `tooLarge`;
+/// use `open_file_chunked` for those.
+#[tauri::command]
+async fn read_file(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: Option<String>,
+    path: String,
+    read_only: Option<bool>,
+) -> Result<String, AppError> {
+    let document = document.or_else(|| app_handle.state::<Tabs>().active(window.label()))
+        .ok_or_else(|| AppError::invalid_argument("The window has no tab to open the file in"))?;
+    let size = fs::metadata(&path).map_err(|e| AppError::io(e, &path))?.len();
+    if app_handle.state::<AppSettings>().get().large_files.should_stream(size) {
+        return Err(AppError::new(ErrorKind::TooLarge, format!("File is {} bytes; open it with open_file_chunked", size)).with_path(&path));
+    }
+    let loaded = documents::load_document(std::path::Path::new(&path))
+        .map_err(|e| AppError::io(e, &path))?
+        .with_read_only(read_only.unwrap_or(false));
+    record_document(&app_handle, &document, DocumentState::loaded(&path, &loaded));
+    let mut info = loaded.info();
+    info.view = app_handle.state::<DocumentViews>().get(std::path::Path::new(&path));
+    let open = PendingOpen::Document(Some(path), loaded.content.clone(), info);
+    window.emit_to(window.label(), "tab-opened", PendingTab { id: document, open: Some(open) })?;
+    Ok(loaded.content)
+}
+
+/// Stream a large file into the window as `file-open-progress` events, followed by
+/// `file-open-complete` with the document id and info, or `file-open-cancelled` if
+/// `cancel_file_open` was called first. With `preview: true` only the first
+/// `previewMb` megabytes are loaded and the document is locked against saving.
+/// A window streams one file at a time.
 #[tauri::command]
-async fn save_file(window: tauri::Window, path: String, content: String) -> Result<(), String> {
-    fs::write(&path, content).map_err(|e| e.to_string())?;
-    window.emit_to(window.label(), "file-saved", path).map_err(|e| e.to_string())?;
+async fn open_file_chunked(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    preview: Option<bool>,
+    read_only: Option<bool>,
+) -> Result<(), AppError> {
+    let settings = app_handle.state::<AppSettings>().get().large_files;
+    let limit = preview.unwrap_or(false).then(|| settings.preview_bytes());
+    let read_only = read_only.unwrap_or(false);
+    let window_label = window.label().to_string();
+    let cancel = app_handle.state::<ChunkedOpens>().start(&window_label);
+
+    std::thread::spawn(move || {
+        let started = std::time::Instant::now();
+        let target = std::path::Path::new(&path);
+        let result = large_files::stream_document(target, limit, &cancel, |progress| {
+            let _ = window.emit_to(&window_label, "file-open-progress", progress);
+        });
+        app_handle.state::<ChunkedOpens>().finish(&window_label, &cancel);
+
+        match result {
+            // The tab was closed while the file streamed in
+            Ok(Some(_)) if app_handle.state::<Tabs>().window_of(&document).is_none() => {
+                println!("Dropped streamed {}; its tab is gone", path);
+            }
+            Ok(Some(streamed)) => {
+                println!("Streamed {} ({} bytes) in {:?}", path, streamed.fingerprint.size, started.elapsed());
+                let read_only = if streamed.truncated {
+                    Some(ReadOnly::Partial)
+                } else if !documents::is_writable(target) {
+                    Some(ReadOnly::NotWritable)
+                } else {
+                    read_only.then_some(ReadOnly::Requested)
+                };
+                record_document(&app_handle, &document, DocumentState {
+                    path: path.clone(),
+                    fingerprint: streamed.fingerprint,
+                    encoding: streamed.encoding.clone(),
+                    line_ending: streamed.line_endings.dominant,
+                    read_only,
+                    // A preview holds only part of the file, so external changes still
+                    // arrive as the whole text
+                    base: (!streamed.truncated).then(|| Arc::from(streamed.content)),
+                });
+                let info = DocumentInfo {
+                    encoding: streamed.encoding,
+                    line_endings: streamed.line_endings,
+                    recovered: false,
+                    unsaved: false,
+                    read_only,
+                    view: app_handle.state::<DocumentViews>().get(target),
+                };
+                let _ = window.emit_to(&window_label, "file-open-complete", (document, path, info));
+            }
+            Ok(None) => {
+                println!("Cancelled opening {} after {:?}", path, started.elapsed());
+                let _ = window.emit_to(&window_label, "file-open-cancelled", path);
+            }
+            Err(e) => emit_file_error(&window, &window_label, "open", AppError::io(e, &path)),
+        }
+    });
     Ok(())
 }
 
+/// Stop the window's `open_file_chunked` in progress. Returns false if there was none.
+#[tauri::command]
+async fn cancel_file_open(window: tauri::Window, app_handle: tauri::AppHandle) -> Result<bool, AppError> {
+    Ok(app_handle.state::<ChunkedOpens>().cancel(window.label()))
+}
+
+/// "Reopen with Encoding…": decode the file again with an encoding chosen by the user,
+/// for files where detection guessed wrong. Unsaved edits in the document are replaced.
+#[tauri::command]
+async fn reopen_with_encoding(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    encoding: String,
+) -> Result<String, AppError> {
+    let text_encoding = TextEncoding::from_label(&encoding, false)
+        .ok_or_else(|| AppError::invalid_argument(format!("Unknown encoding: {}", encoding)))?;
+    let requested_read_only = app_handle.state::<OpenDocuments>().get(&document)
+        .is_some_and(|doc| doc.path == path && doc.read_only == Some(ReadOnly::Requested));
+    let loaded = documents::load_document_with_encoding(std::path::Path::new(&path), &text_encoding)
+        .map_err(|e| AppError::io(e, &path))?
+        .with_read_only(requested_read_only);
+    println!("Reopened {} as {}", path, loaded.encoding.name);
+    record_document(&app_handle, &document, DocumentState::loaded(&path, &loaded));
+    let open = PendingOpen::Document(Some(path), loaded.content.clone(), loaded.info());
+    window.emit_to(window.label(), "tab-opened", PendingTab { id: document, open: Some(open) })?;
+    Ok(loaded.content)
+}
+
+/// Encodings the frontend can offer in the "Reopen/Save with Encoding…" pickers
 #[tauri::command]
-async fn read_file(window: tauri::Window, path: String) -> Result<String, String> {
-    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    window.emit_to(window.label(), "file-opened", (path, content.clone())).map_err(|e| e.to_string())?;
+async fn list_encodings() -> Result<Vec<String>, AppError> {
+    Ok(encoding::SUPPORTED_ENCODINGS.iter().map(|name| name.to_string()).collect())
+}
+
+/// Called by the frontend after it reloads a file that changed externally, so the
+/// new disk state becomes the baseline for conflict detection on the next save.
+#[tauri::command]
+async fn acknowledge_external_change(app_handle: tauri::AppHandle, document: String, path: String) -> Result<(), AppError> {
+    let open_documents = app_handle.state::<OpenDocuments>();
+    let known = open_documents.get(&document).filter(|doc| doc.path == path);
+    let disk_path = std::path::Path::new(&path);
+    if !disk_path.exists() {
+        println!("acknowledge_external_change: {} no longer exists", path);
+        return Ok(());
+    }
+    // Decode with the encoding already in use so a reload never flips it
+    let requested_read_only = known.as_ref().is_some_and(|doc| doc.read_only == Some(ReadOnly::Requested));
+    let loaded = match known {
+        Some(doc) => documents::load_document_with_encoding(disk_path, &doc.encoding),
+        None => documents::load_document(disk_path),
+    }.map_err(|e| AppError::io(e, &path))?.with_read_only(requested_read_only);
+    open_documents.record(&document, DocumentState::loaded(&path, &loaded));
+    Ok(())
+}
+
+/// Merge a file that changed externally into the document's unsaved `content`, using
+/// the text last loaded or saved as the common base. Conflicting lines are left
+/// between markers and listed in the result. Nothing is recorded: once the editor
+/// shows the merge, `acknowledge_external_change` makes the disk version the new base.
+#[tauri::command]
+async fn merge_external_change(
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    content: String,
+) -> Result<MergeOutcome, AppError> {
+    let known = app_handle.state::<OpenDocuments>().get(&document).filter(|doc| doc.path == path);
+    let (encoding, base) = match known {
+        Some(DocumentState { encoding, base: Some(base), .. }) => (encoding, base),
+        _ => return Err(AppError::invalid_argument("No base version to merge against").with_path(&path)),
+    };
+    let disk = documents::load_document_with_encoding(std::path::Path::new(&path), &encoding)
+        .map_err(|e| AppError::io(e, &path))?;
+    let outcome = merge::merge_texts(&base, &content, &disk.content);
+    println!("Merged external change to {} with {} conflict(s)", path, outcome.conflicts.len());
+    Ok(outcome)
+}
+
+/// "Convert Line Endings": rewrite the editor content with the given line ending and
+/// make it the document's style for later saves under the "preserve" policy.
+#[tauri::command]
+async fn convert_line_endings(
+    app_handle: tauri::AppHandle,
+    document: String,
+    content: String,
+    line_ending: LineEnding,
+) -> Result<String, AppError> {
+    let open_documents = app_handle.state::<OpenDocuments>();
+    if let Some(mut doc) = open_documents.get(&document) {
+        doc.line_ending = line_ending;
+        open_documents.record(&document, doc);
+    }
+    Ok(line_endings::normalize(&content, line_ending))
+}
+
+#[tauri::command]
+async fn get_settings(app_handle: tauri::AppHandle) -> Result<Settings, AppError> {
+    Ok(app_handle.state::<AppSettings>().get())
+}
+
+/// Replace the settings and persist them to the app config directory
+#[tauri::command]
+async fn update_settings(app_handle: tauri::AppHandle, settings: Settings) -> Result<(), AppError> {
+    if let Some(path) = settings_path(&app_handle) {
+        settings.save(&path)?;
+    }
+    app_handle.state::<AppSettings>().set(settings);
+    Ok(())
+}
+
+/// Backups of `path` kept by backup-on-save, most recent first within each location
+#[tauri::command]
+async fn list_backups(app_handle: tauri::AppHandle, path: String) -> Result<Vec<BackupInfo>, AppError> {
+    Ok(backups::list_backups(std::path::Path::new(&path), backups_dir(&app_handle).as_deref()))
+}
+
+/// Read a backup of `path` with the document's encoding. The content is returned to
+/// the editor as unsaved changes rather than written to disk, so restoring is undoable
+/// and the next save backs up the version it replaces.
+#[tauri::command]
+async fn restore_backup(
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: String,
+    backup: String,
+) -> Result<String, AppError> {
+    // Only files listed as backups of this document may be read back
+    let known_backup = backups::list_backups(std::path::Path::new(&path), backups_dir(&app_handle).as_deref())
+        .into_iter()
+        .any(|info| info.path == backup);
+    if !known_backup {
+        return Err(AppError::invalid_argument(format!("Not a backup of {}", path)).with_path(&backup));
+    }
+    let backup_path = std::path::Path::new(&backup);
+    let known = app_handle.state::<OpenDocuments>().get(&document).filter(|doc| doc.path == path);
+    let loaded = match known {
+        Some(doc) => documents::load_document_with_encoding(backup_path, &doc.encoding),
+        None => documents::load_document(backup_path),
+    }.map_err(|e| AppError::io(e, backup_path))?;
+    println!("Restored backup {} for {}", backup, path);
+    Ok(loaded.content)
+}
+
+/// Local history snapshots of `path`, newest first
+#[tauri::command]
+async fn list_history(app_handle: tauri::AppHandle, path: String) -> Result<Vec<Snapshot>, AppError> {
+    app_handle.state::<LocalHistory>().list(&path).map_err(|e| AppError::io(e, &path))
+}
+
+/// Text of a snapshot, or an `invalidArgument` error if `path` has no such snapshot
+fn snapshot_content(app_handle: &tauri::AppHandle, path: &str, snapshot: &str) -> Result<String, AppError> {
+    app_handle.state::<LocalHistory>().content(path, snapshot)
+        .map_err(|e| AppError::io(e, path))?
+        .ok_or_else(|| AppError::invalid_argument(format!("Unknown history snapshot: {}", snapshot)).with_path(path))
+}
+
+/// Unified diff between two snapshots of `path`
+#[tauri::command]
+async fn diff_snapshots(app_handle: tauri::AppHandle, path: String, from: String, to: String) -> Result<String, AppError> {
+    let old = snapshot_content(&app_handle, &path, &from)?;
+    let new = snapshot_content(&app_handle, &path, &to)?;
+    Ok(history::unified_diff(&from, &old, &to, &new))
+}
+
+/// Unified diff from a snapshot of `path` to the editor's current buffer
+#[tauri::command]
+async fn diff_snapshot_with_buffer(
+    app_handle: tauri::AppHandle,
+    path: String,
+    snapshot: String,
+    content: String,
+) -> Result<String, AppError> {
+    let old = snapshot_content(&app_handle, &path, &snapshot)?;
+    Ok(history::unified_diff(&snapshot, &old, "buffer", &content))
+}
+
+/// Text of a snapshot, returned to the editor as unsaved changes like `restore_backup`
+#[tauri::command]
+async fn restore_snapshot(app_handle: tauri::AppHandle, path: String, snapshot: String) -> Result<String, AppError> {
+    let content = snapshot_content(&app_handle, &path, &snapshot)?;
+    println!("Restored history snapshot {} for {}", snapshot, path);
     Ok(content)
 }
 
+/// Mark a snapshot as a named checkpoint, or clear its label with `null`
 #[tauri::command]
-async fn update_theme_menu(app_handle: tauri::AppHandle, is_dark: bool) -> Result<(), tauri::Error> {
-    println!("update_theme_menu called with is_dark: {}", is_dark);
+async fn label_snapshot(
+    app_handle: tauri::AppHandle,
+    path: String,
+    snapshot: String,
+    label: Option<String>,
+) -> Result<Snapshot, AppError> {
+    app_handle.state::<LocalHistory>().set_label(&path, &snapshot, label)
+        .map_err(|e| AppError::io(e, &path))?
+        .ok_or_else(|| AppError::invalid_argument(format!("Unknown history snapshot: {}", snapshot)).with_path(&path))
+}
+
+/// "Open as Editable Copy": detach a document from its read-only file, so the
+/// content becomes an untitled document that has to be saved somewhere else.
+#[tauri::command]
+async fn open_as_editable_copy(window: tauri::Window, app_handle: tauri::AppHandle, document: String) -> Result<(), AppError> {
+    if let Some(doc) = app_handle.state::<OpenDocuments>().get(&document) {
+        app_handle.state::<WatcherService>().unsubscribe(window.label(), &doc.path);
+    }
+    app_handle.state::<OpenDocuments>().remove(&document);
+    println!("Document {} detached from its file as an editable copy", document);
+    Ok(())
+}
+
+/// Called by the frontend whenever a document's buffer has unsaved changes. The content
+/// is written to the recovery journal once edits settle, not on every call.
+#[tauri::command]
+async fn update_recovery_buffer(
+    app_handle: tauri::AppHandle,
+    document: String,
+    path: Option<String>,
+    content: String,
+) -> Result<(), AppError> {
+    let journal_document = app_handle.state::<OpenDocuments>().get(&document)
+        .filter(|doc| path.as_deref() == Some(doc.path.as_str()))
+        .map(|doc| JournalDocument {
+            fingerprint: doc.fingerprint,
+            encoding: doc.encoding,
+            line_ending: doc.line_ending,
+        });
+    app_handle.state::<RecoveryJournal>().update(&document, path, content, journal_document);
+    Ok(())
+}
+
+/// Called by the frontend when the user throws away a document's unsaved changes
+#[tauri::command]
+async fn discard_recovery_buffer(app_handle: tauri::AppHandle, document: String) -> Result<(), AppError> {
+    app_handle.state::<RecoveryJournal>().discard(&document);
+    Ok(())
+}
+
+/// List the open documents in the Window menu again, after one was opened, closed
+/// or renamed. Runs on the main thread, where the menu lives.
+fn refresh_window_menu(app_handle: &tauri::AppHandle) {
+    let app = app_handle.clone();
+    let _ = app_handle.run_on_main_thread(move || {
+        if let Err(e) = list_window_menu_documents(&app) {
+            eprintln!("Failed to update the Window menu: {}", e);
+        }
+    });
+}
+
+/// Move the Window menu's checkmark and unsaved-changes marks to match the
+/// documents, without changing which items it has
+fn update_window_menu(app_handle: &tauri::AppHandle) {
+    let app = app_handle.clone();
+    let _ = app_handle.run_on_main_thread(move || {
+        if let Err(e) = mark_window_menu_documents(&app) {
+            eprintln!("Failed to update the Window menu: {}", e);
+        }
+    });
+}
+
+/// The Window menu's window commands. The open documents are listed below them by
+/// `list_window_menu_documents`.
+fn build_window_menu(app_handle: &tauri::AppHandle) -> tauri::Result<Submenu<tauri::Wry>> {
+    let submenu = SubmenuBuilder::new(app_handle, "Window")
+        .item(&MenuItemBuilder::new("Minimize").id("minimize").accelerator("CmdOrCtrl+M").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Zoom").id("zoom").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Cycle Through Windows").id("cycle_windows").accelerator("CmdOrCtrl+`").build(app_handle)?)
+        .separator()
+        .item(&MenuItemBuilder::new("Bring All to Front").id("bring_all_to_front").build(app_handle)?)
+        .build()?;
+
+    {
+        let window_menu = app_handle.state::<WindowMenu>();
+        let mut items = window_menu.0.lock().unwrap();
+        let focused = items.take().and_then(|items| items.focused);
+        *items = Some(WindowMenuItems { submenu: submenu.clone(), separator: None, documents: Vec::new(), focused });
+    }
+    list_window_menu_documents(app_handle)?;
+    Ok(submenu)
+}
+
+/// Replace the Window menu's document items with one per open document, in window
+/// order and tab order within each window
+fn list_window_menu_documents(app_handle: &tauri::AppHandle) -> tauri::Result<()> {
+    {
+        let window_menu = app_handle.state::<WindowMenu>();
+        let mut items = window_menu.0.lock().unwrap();
+        let Some(menu) = items.as_mut() else { return Ok(()) };
+        for (_, item) in menu.documents.drain(..) {
+            menu.submenu.remove(&item)?;
+        }
+        if let Some(separator) = menu.separator.take() {
+            menu.submenu.remove(&separator)?;
+        }
+
+        let tabs = app_handle.state::<Tabs>();
+        let open_documents = app_handle.state::<OpenDocuments>();
+        let mut labels: Vec<String> = app_handle.webview_windows().into_keys().collect();
+        labels.sort_by_key(|label| window_number(label));
+        let documents: Vec<String> = labels.iter().flat_map(|label| tabs.list(label).tabs).collect();
+        if !documents.is_empty() {
+            let separator = PredefinedMenuItem::separator(app_handle)?;
+            menu.submenu.append(&separator)?;
+            menu.separator = Some(separator);
+        }
+        for document in documents {
+            let path = open_documents.0.lock().unwrap().get(&document).map(|doc| doc.path.clone());
+            let item = CheckMenuItemBuilder::new(document_name(path.as_deref()))
+                .id(format!("{}{}", DOCUMENT_MENU_PREFIX, document))
+                .build(app_handle)?;
+            menu.submenu.append(&item)?;
+            menu.documents.push((document, item));
+        }
+    }
+    mark_window_menu_documents(app_handle)
+}
+
+/// Check the document in front of the focused window and mark the ones with
+/// unsaved changes
+fn mark_window_menu_documents(app_handle: &tauri::AppHandle) -> tauri::Result<()> {
+    let window_menu = app_handle.state::<WindowMenu>();
+    let items = window_menu.0.lock().unwrap();
+    let Some(menu) = items.as_ref() else { return Ok(()) };
+    let tabs = app_handle.state::<Tabs>();
+    let open_documents = app_handle.state::<OpenDocuments>();
+    let dirty = app_handle.state::<DirtyDocuments>().0.lock().unwrap().clone();
+    let front = menu.focused.as_deref().and_then(|label| tabs.active(label));
+    for (document, item) in &menu.documents {
+        let path = open_documents.0.lock().unwrap().get(document).map(|doc| doc.path.clone());
+        let mut text = document_name(path.as_deref());
+        if dirty.contains(document) {
+            text.push_str(" •");
+        }
+        item.set_text(text)?;
+        item.set_checked(front.as_ref() == Some(document))?;
+    }
+    Ok(())
+}
 
-    // Rebuild the entire menu with updated theme text
+/// Build the application menu bar. The theme item's label depends on the current theme.
+fn build_menu(app_handle: &tauri::AppHandle, is_dark: bool) -> tauri::Result<Menu<tauri::Wry>> {
     let theme_text = if is_dark {
         "Switch to Light Mode"
     } else {
         "Switch to Dark Mode"
     };
 
-    // Rebuild the menu
-    let app_menu = SubmenuBuilder::new(&app_handle, "Mark-us-Down")
-        .item(&MenuItemBuilder::new("About Mark-us-Down").id("about").build(&app_handle)?)
+    let app_menu = SubmenuBuilder::new(app_handle, "Mark-us-Down")
+        .item(&MenuItemBuilder::new("About Mark-us-Down").id("about").build(app_handle)?)
         .separator()
-        .item(&MenuItemBuilder::new("Quit Mark-us-Down").id("quit").accelerator("CmdOrCtrl+Q").build(&app_handle)?)
+        .item(&MenuItemBuilder::new("Quit Mark-us-Down").id("quit").accelerator("CmdOrCtrl+Q").build(app_handle)?)
+        .build()?;
+
+    let line_endings_menu = SubmenuBuilder::new(app_handle, "Convert Line Endings")
+        .item(&MenuItemBuilder::new("LF (Unix/macOS)").id("convert_eol_lf").build(app_handle)?)
+        .item(&MenuItemBuilder::new("CRLF (Windows)").id("convert_eol_crlf").build(app_handle)?)
         .build()?;
 
-    let file_menu = SubmenuBuilder::new(&app_handle, "File")
-        .item(&MenuItemBuilder::new("New Window").id("new_window").accelerator("CmdOrCtrl+Shift+N").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("New").id("new").accelerator("CmdOrCtrl+N").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Open...").id("open").accelerator("CmdOrCtrl+O").build(&app_handle)?)
+    let file_menu = SubmenuBuilder::new(app_handle, "File")
+        .item(&MenuItemBuilder::new("New Window").id("new_window").accelerator("CmdOrCtrl+Shift+N").build(app_handle)?)
+        .item(&MenuItemBuilder::new("New Tab").id("new_tab").accelerator("CmdOrCtrl+Alt+N").build(app_handle)?)
+        .item(&MenuItemBuilder::new("New").id("new").accelerator("CmdOrCtrl+N").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Open...").id("open").accelerator("CmdOrCtrl+O").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Open Read-Only...").id("open_read_only").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Open in New Window...").id("open_new_window").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Reopen with Encoding...").id("reopen_with_encoding").build(app_handle)?)
         .separator()
-        .item(&MenuItemBuilder::new("Save").id("save").accelerator("CmdOrCtrl+S").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Save As...").id("save_as").accelerator("CmdOrCtrl+Shift+S").build(&app_handle)?)
+        .item(&MenuItemBuilder::new("Save").id("save").accelerator("CmdOrCtrl+S").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Save As...").id("save_as").accelerator("CmdOrCtrl+Shift+S").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Save with Encoding...").id("save_with_encoding").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Restore from Backup...").id("restore_backup").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Local History...").id("local_history").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Label Checkpoint...").id("label_checkpoint").build(app_handle)?)
+        .item(&line_endings_menu)
         .separator()
-        .item(&MenuItemBuilder::new("Print...").id("print").accelerator("CmdOrCtrl+P").build(&app_handle)?)
+        .item(&MenuItemBuilder::new("Print...").id("print").accelerator("CmdOrCtrl+P").build(app_handle)?)
         .separator()
-        .item(&MenuItemBuilder::new("Close").id("close").accelerator("CmdOrCtrl+W").build(&app_handle)?)
+        .item(&MenuItemBuilder::new("Move Tab to New Window").id("move_tab_new_window").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Close Tab").id("close").accelerator("CmdOrCtrl+W").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Close Window").id("close_window").accelerator("CmdOrCtrl+Shift+W").build(app_handle)?)
         .build()?;
 
-    let edit_menu = SubmenuBuilder::new(&app_handle, "Edit")
-        .item(&MenuItemBuilder::new("Undo").id("undo").accelerator("CmdOrCtrl+Z").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Redo").id("redo").accelerator("CmdOrCtrl+Shift+Z").build(&app_handle)?)
+    let edit_menu = SubmenuBuilder::new(app_handle, "Edit")
+        .item(&MenuItemBuilder::new("Undo").id("undo").accelerator("CmdOrCtrl+Z").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Redo").id("redo").accelerator("CmdOrCtrl+Shift+Z").build(app_handle)?)
         .separator()
-        .item(&PredefinedMenuItem::cut(&app_handle, None)?)
-        .item(&PredefinedMenuItem::copy(&app_handle, None)?)
-        .item(&PredefinedMenuItem::paste(&app_handle, None)?)
+        .item(&PredefinedMenuItem::cut(app_handle, None)?)
+        .item(&PredefinedMenuItem::copy(app_handle, None)?)
+        .item(&PredefinedMenuItem::paste(app_handle, None)?)
         .separator()
-        .item(&PredefinedMenuItem::select_all(&app_handle, None)?)
+        .item(&PredefinedMenuItem::select_all(app_handle, None)?)
         .build()?;
 
-    let view_menu_builder = SubmenuBuilder::new(&app_handle, "View")
-        .item(&MenuItemBuilder::new(theme_text).id("theme_toggle").accelerator("CmdOrCtrl+T").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Toggle Reading Mode").id("reading_mode").accelerator("CmdOrCtrl+E").build(&app_handle)?)
+    let view_menu_builder = SubmenuBuilder::new(app_handle, "View")
+        .item(&MenuItemBuilder::new(theme_text).id("theme_toggle").accelerator("CmdOrCtrl+T").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Toggle Reading Mode").id("reading_mode").accelerator("CmdOrCtrl+E").build(app_handle)?)
+        .separator()
+        .item(&MenuItemBuilder::new("Zoom In").id("zoom_in").accelerator("CmdOrCtrl+Plus").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Zoom Out").id("zoom_out").accelerator("CmdOrCtrl+-").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Reset Zoom").id("reset_zoom").accelerator("CmdOrCtrl+0").build(app_handle)?)
         .separator()
-        .item(&MenuItemBuilder::new("Zoom In").id("zoom_in").accelerator("CmdOrCtrl+Plus").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Zoom Out").id("zoom_out").accelerator("CmdOrCtrl+-").build(&app_handle)?)
-        .item(&MenuItemBuilder::new("Reset Zoom").id("reset_zoom").accelerator("CmdOrCtrl+0").build(&app_handle)?);
+        .item(&MenuItemBuilder::new("Show Next Tab").id("next_tab").accelerator("CmdOrCtrl+Shift+]").build(app_handle)?)
+        .item(&MenuItemBuilder::new("Show Previous Tab").id("previous_tab").accelerator("CmdOrCtrl+Shift+[").build(app_handle)?);
 
     #[cfg(debug_assertions)]
     let view_menu_builder = view_menu_builder
         .separator()
-        .item(&MenuItemBuilder::new("Debug Info").id("debug_info").build(&app_handle)?);
+        .item(&MenuItemBuilder::new("Debug Info").id("debug_info").build(app_handle)?);
 
     let view_menu = view_menu_builder.build()?;
 
-    let menu = MenuBuilder::new(&app_handle)
+    MenuBuilder::new(app_handle)
         .item(&app_menu)
         .item(&file_menu)
         .item(&edit_menu)
         .item(&view_menu)
-        .build()?;
+        .item(&build_window_menu(app_handle)?)
+        .build()
+}
+
+#[tauri::command]
+async fn update_theme_menu(app_handle: tauri::AppHandle, is_dark: bool) -> Result<(), AppError> {
+    println!("update_theme_menu called with is_dark: {}", is_dark);
 
-    app_handle.set_menu(menu)?;
-    println!("Menu rebuilt with theme text: {}", theme_text);
+    // Rebuild the entire menu with updated theme text, on the main thread where the
+    // Window menu's items are updated
+    let app = app_handle.clone();
+    app_handle.run_on_main_thread(move || {
+        match build_menu(&app, is_dark).and_then(|menu| app.set_menu(menu)) {
+            Ok(_) => println!("Menu rebuilt for {} theme", if is_dark { "dark" } else { "light" }),
+            Err(e) => eprintln!("Failed to rebuild menu: {}", e),
+        }
+    })?;
 
     Ok(())
 }
 
 #[tauri::command]
-async fn debug_args() -> Result<Vec<String>, String> {
+async fn debug_args() -> Result<Vec<String>, AppError> {
     let args: Vec<String> = std::env::args().collect();
     println!("Debug args called - found {} arguments:", args.len());
     for (i, arg) in args.iter().enumerate() {
@@ -227,107 +1531,381 @@ async fn debug_args() -> Result<Vec<String>, String> {
     Ok(args)
 }
 
+/// Watch the file open in a window. Windows showing the same file share one watch
+/// in the `WatcherService`, which follows the file across renames and atomic saves.
+/// Returns whether the file is watched natively or polled, and why.
 #[tauri::command]
-async fn start_file_watcher(window: tauri::Window, app_handle: tauri::AppHandle, file_path: String) -> Result<(), String> {
-    let watchers: FileWatchers = app_handle.state::<FileWatchers>().inner().clone();
+async fn start_file_watcher(window: tauri::Window, app_handle: tauri::AppHandle, file_path: String) -> Result<WatchInfo, AppError> {
     let window_label = window.label().to_string();
 
     let path = PathBuf::from(&file_path);
     if !path.exists() {
-        return Err("File does not exist".to_string());
+        return Err(AppError::not_found(&path));
     }
 
-    // Create a unique key for this window's watcher
-    let watcher_key = format!("{}:{}", window_label, file_path);
+    let settings = app_handle.state::<AppSettings>().get().watching;
+    let info = app_handle.state::<WatcherService>()
+        .subscribe(&window_label, &file_path, &settings)
+        .map_err(|e| AppError::watcher(format!("Failed to watch file: {}", e), &path))?;
 
-    let (tx, rx) = std::sync::mpsc::channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())
-        .map_err(|e| format!("Failed to create watcher: {}", e))?;
+    println!("Started watching file: {} for window: {} ({:?}, {:?})", file_path, window_label, info.strategy, info.reason);
+    Ok(info)
+}
 
-    watcher.watch(&path, RecursiveMode::NonRecursive)
-        .map_err(|e| format!("Failed to watch file: {}", e))?;
+#[tauri::command]
+async fn stop_file_watcher(window: tauri::Window, app_handle: tauri::AppHandle, file_path: String) -> Result<(), AppError> {
+    let window_label = window.label().to_string();
 
-    // Store the watcher
-    {
-        let mut watchers_lock = watchers.lock().unwrap();
-        watchers_lock.insert(watcher_key.clone(), watcher);
+    if app_handle.state::<WatcherService>().unsubscribe(&window_label, &file_path) {
+        println!("Stopped watching file: {} for window: {}", file_path, window_label);
+    } else {
+        // Not an error - the watcher might have already been removed
+        println!("File watcher not found for: {} in window: {}", file_path, window_label);
     }
+    Ok(())
+}
 
-    // Start the event loop in a separate thread
-    let file_path_clone = file_path.clone();
-    let window_clone = window.clone();
-    let window_label_clone = window_label.clone();
-    let watchers_clone = watchers.clone();
-    let watcher_key_clone = watcher_key.clone();
+/// Watch the local images and files the window's document links to, so the preview
+/// can refresh when they change on disk (`asset-changed`). Called with the editor
+/// content once it settles; assets that are no longer linked stop being watched.
+/// Returns the watched paths.
+#[tauri::command]
+async fn watch_assets(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    content: String,
+) -> Result<Vec<String>, AppError> {
+    let document = path.as_deref().map(std::path::Path::new);
+    let targets: Vec<PathBuf> = assets::local_targets(&content, document.and_then(|p| p.parent()))
+        .into_iter()
+        .filter(|target| Some(target.as_path()) != document)
+        .collect();
+    let settings = app_handle.state::<AppSettings>().get().watching;
+    Ok(app_handle.state::<WatcherService>().set_assets(window.label(), &targets, &settings))
+}
 
-    std::thread::spawn(move || {
-        for res in rx {
-            match res {
-                Ok(event) => {
-                    if let Event { kind: notify::EventKind::Modify(_), paths, .. } = event {
-                        if paths.iter().any(|p| p.to_string_lossy() == file_path_clone) {
-                            // File was modified, read new content and emit event to the specific window
-                            if let Ok(content) = fs::read_to_string(&file_path_clone) {
-                                let _ = window_clone.emit_to(&window_label_clone, "file-changed-externally", (&file_path_clone, &content));
-                            }
-                        }
+/// Files being watched and the windows subscribed to each, for debugging
+#[tauri::command]
+async fn debug_watchers(app_handle: tauri::AppHandle) -> Result<Vec<WatchSubscription>, AppError> {
+    let subscriptions = app_handle.state::<WatcherService>().subscriptions();
+    println!("Debug watchers called - {} watched files:", subscriptions.len());
+    for subscription in &subscriptions {
+        let windows: Vec<&str> = subscription.subscribers.iter().map(|s| s.window_label.as_str()).collect();
+        println!("  {} -> {}", subscription.path, windows.join(", "));
+    }
+    Ok(subscriptions)
+}
+
+/// Pass notices from the watcher service on to the subscribed windows. Changes to
+/// linked assets are gathered into one `asset-changed` event per window.
+fn handle_watch_notices(app_handle: &tauri::AppHandle, notices: Vec<WatchNotice>) {
+    let mut changed_assets: HashMap<String, Vec<String>> = HashMap::new();
+    for notice in notices {
+        let (path, change, subscribers) = match notice {
+            WatchNotice::Changed { path, change, subscribers } => (path, change, subscribers),
+            WatchNotice::Failed { error, subscribers } => {
+                for subscriber in subscribers {
+                    emit_file_error(app_handle, &subscriber.window_label, "watch", AppError::watcher(&error, &subscriber.path));
+                }
+                continue;
+            }
+            WatchNotice::Rearmed { error, subscribers, dropped } => {
+                // The error touched every watch, so each window hears about it once
+                let mut told = HashSet::new();
+                for subscriber in subscribers.iter().filter(|s| s.kind == SubscriptionKind::Document) {
+                    if told.insert(subscriber.window_label.clone()) {
+                        let message = format!("File watching was interrupted and has been restarted; changes made meanwhile may have been missed ({})", error);
+                        emit_file_error(app_handle, &subscriber.window_label, "watch", AppError::watcher(message, &subscriber.path));
                     }
                 }
-                Err(e) => {
-                    eprintln!("File watcher error: {}", e);
-                    // Remove watcher on error
-                    let mut watchers_lock = watchers_clone.lock().unwrap();
-                    watchers_lock.remove(&watcher_key_clone);
-                    break;
+                for subscriber in dropped {
+                    emit_file_error(app_handle, &subscriber.window_label, "watch", AppError::watcher(&error, &subscriber.path));
                 }
+                continue;
             }
+        };
+        let (document_subscribers, asset_subscribers): (Vec<Subscriber>, Vec<Subscriber>) = subscribers.into_iter()
+            .partition(|s| s.kind == SubscriptionKind::Document);
+        for asset in asset_subscribers {
+            changed_assets.entry(asset.window_label).or_default().push(asset.path);
         }
-    });
-
-    println!("Started watching file: {} for window: {}", file_path, window_label);
-    Ok(())
+        handle_document_change(app_handle, path, change, document_subscribers);
+    }
+    for (window_label, paths) in changed_assets {
+        println!("Linked assets changed in window {}: {:?}", window_label, paths);
+        let _ = app_handle.emit_to(window_label.as_str(), "asset-changed", paths);
+    }
 }
 
-#[tauri::command]
-async fn stop_file_watcher(window: tauri::Window, app_handle: tauri::AppHandle, file_path: String) -> Result<(), String> {
-    let watchers: FileWatchers = app_handle.state::<FileWatchers>().inner().clone();
-    let window_label = window.label().to_string();
-    let watcher_key = format!("{}:{}", window_label, file_path);
-
-    let mut watchers_lock = watchers.lock().unwrap();
+/// The document in one of a window's tabs that is the file at `path`, as the window
+/// subscribed to it
+fn document_in_window(app_handle: &tauri::AppHandle, window_label: &str, path: &str) -> Option<(String, DocumentState)> {
+    let open_documents = app_handle.state::<OpenDocuments>();
+    app_handle.state::<Tabs>().list(window_label).tabs.into_iter()
+        .find_map(|document| open_documents.get(&document).filter(|doc| doc.path == path).map(|doc| (document, doc)))
+}
 
-    if watchers_lock.remove(&watcher_key).is_some() {
-        println!("Stopped watching file: {} for window: {}", file_path, window_label);
-        Ok(())
-    } else {
-        // Not an error - the watcher might have already been removed
-        println!("File watcher not found for: {} in window: {}", file_path, window_label);
-        Ok(())
+/// Report a change to a watched document to the windows showing it, naming it by
+/// path so the window can find the tab. Content changes are only reported when they
+/// differ from what the document last loaded or saved, so the app's own saves don't
+/// come back as external changes.
+fn handle_document_change(app_handle: &tauri::AppHandle, path: PathBuf, change: FileChange, subscribers: Vec<Subscriber>) {
+    if subscribers.is_empty() {
+        return;
+    }
+    let file_path = path.to_string_lossy().to_string();
+    let open_documents = app_handle.state::<OpenDocuments>();
+
+    match change {
+        FileChange::Modified | FileChange::Restored => {
+            // One history snapshot per change, however many windows show the file
+            let mut recorded = false;
+            for subscriber in subscribers {
+                let label = &subscriber.window_label;
+                let known = document_in_window(app_handle, label, &subscriber.path).map(|(_, doc)| doc);
+                // Decode with the encoding already in use so a reload never flips it
+                let loaded = match &known {
+                    Some(doc) => documents::load_document_with_encoding(&path, &doc.encoding),
+                    None => documents::load_document(&path),
+                };
+                let Ok(loaded) = loaded else { continue };
+                let known_hash = known.as_ref().map(|doc| doc.fingerprint.hash.as_str());
+                let external = app_handle.state::<WatcherService>()
+                    .is_external(&path, label, &loaded.fingerprint.hash, known_hash);
+                if external && !recorded {
+                    record_history(app_handle, &subscriber.path, &loaded.content, SnapshotSource::External);
+                    recorded = true;
+                }
+                if change == FileChange::Restored {
+                    println!("Watched file reappeared: {} in window: {}", subscriber.path, label);
+                    let _ = app_handle.emit_to(label.as_str(), "file-restored", (&subscriber.path, &loaded.content));
+                } else if external {
+                    // Send only what changed since the version the window last acknowledged
+                    let base = known.as_ref().and_then(|doc| doc.base.as_deref());
+                    let content = ExternalContent::between(base, loaded.content);
+                    let _ = app_handle.emit_to(label.as_str(), "file-changed-externally", (&subscriber.path, content));
+                }
+            }
+        }
+        FileChange::Deleted => {
+            for subscriber in subscribers {
+                println!("Watched file was deleted: {} in window: {}", subscriber.path, subscriber.window_label);
+                let _ = app_handle.emit_to(subscriber.window_label.as_str(), "file-deleted", &subscriber.path);
+            }
+        }
+        FileChange::Renamed { .. } => {
+            for subscriber in subscribers {
+                let label = &subscriber.window_label;
+                println!("Watched file was renamed: {} -> {} in window: {}", subscriber.path, file_path, label);
+                // Saves go to the new path from now on
+                if let Some((document, mut doc)) = document_in_window(app_handle, label, &subscriber.path) {
+                    doc.path = file_path.clone();
+                    open_documents.record(&document, doc);
+                }
+                let _ = app_handle.emit_to(label.as_str(), "file-renamed", (&subscriber.path, &file_path));
+            }
+            refresh_window_menu(app_handle);
+        }
     }
 }
 
+/// Called by the frontend when the window holds nothing but one empty tab, and again
+/// once it doesn't, so an empty window can be reused for the next file opened
 #[tauri::command]
-async fn set_window_empty(window: tauri::Window, app_handle: tauri::AppHandle, is_empty: bool) -> Result<(), String> {
+async fn set_window_empty(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    is_empty: bool,
+) -> Result<(), AppError> {
     let empty_windows: tauri::State<EmptyWindows> = app_handle.state::<EmptyWindows>();
     let mut empty_set = empty_windows.inner().lock().unwrap();
     let label = window.label().to_string();
     if is_empty {
+        // An empty tab no longer holds a document or unsaved changes
+        app_handle.state::<OpenDocuments>().remove(&document);
+        app_handle.state::<RecoveryJournal>().discard(&document);
         empty_set.insert(label.clone());
     } else {
         empty_set.remove(&label);
     }
+    drop(empty_set);
     println!("Window {} empty state updated to: {}", label, is_empty);
+    refresh_window_menu(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_document_dirty(app_handle: tauri::AppHandle, document: String, is_dirty: bool) -> Result<(), AppError> {
+    // A report can arrive after its tab was closed and forgotten
+    if is_dirty && app_handle.state::<Tabs>().window_of(&document).is_none() {
+        println!("Ignoring dirty state for document {} without a tab", document);
+        return Ok(());
+    }
+    let dirty_documents: tauri::State<DirtyDocuments> = app_handle.state::<DirtyDocuments>();
+    let mut dirty_set = dirty_documents.inner().0.lock().unwrap();
+    if is_dirty {
+        dirty_set.insert(document.clone());
+    } else {
+        dirty_set.remove(&document);
+    }
+    drop(dirty_set);
+    println!("Document {} dirty state updated to: {}", document, is_dirty);
+    update_window_menu(&app_handle);
+    Ok(())
+}
+
+/// The frontend's answer to `save-before-close`. Once the document is saved its tab
+/// closes, its window asks about the next document, or the quit moves on; a save
+/// that was cancelled or failed keeps everything open and cancels the quit.
+#[tauri::command]
+async fn finish_save_before_close(app_handle: tauri::AppHandle, document: String, saved: bool) -> Result<(), AppError> {
+    let action = app_handle.state::<PendingCloses>().0.lock().unwrap().remove(&document);
+    if !saved {
+        println!("Document {} was not saved; keeping it open", document);
+        if action == Some(CloseAction::Quit) {
+            QUIT_PENDING.store(false, Ordering::SeqCst);
+        }
+        return Ok(());
+    }
+    // Don't wait for the frontend to report the buffer clean, or closing would ask again
+    app_handle.state::<DirtyDocuments>().0.lock().unwrap().remove(&document);
+    if let Some(action) = action {
+        continue_close(&app_handle, &document, action);
+    }
+    Ok(())
+}
+
+/// Error for a document id that names no open tab
+fn unknown_document(document: &str) -> AppError {
+    AppError::invalid_argument(format!("Unknown document: {}", document))
+}
+
+/// "New Tab": open an empty document in a new tab of the window. Returns its id.
+#[tauri::command]
+async fn new_tab(window: tauri::Window, app_handle: tauri::AppHandle) -> Result<String, AppError> {
+    Ok(open_tab(&app_handle, window.label(), None))
+}
+
+/// Called by the frontend when the user brings a tab to the front
+#[tauri::command]
+async fn activate_tab(app_handle: tauri::AppHandle, document: String) -> Result<(), AppError> {
+    app_handle.state::<Tabs>().activate(&document).ok_or_else(|| unknown_document(&document))?;
+    update_window_menu(&app_handle);
+    Ok(())
+}
+
+/// Close a document's tab, asking about unsaved changes first. The window answers
+/// with `tab-closed` and `tab-activate`, or closes along with its last tab.
+#[tauri::command]
+async fn close_tab(app_handle: tauri::AppHandle, document: String) -> Result<(), AppError> {
+    if app_handle.state::<Tabs>().window_of(&document).is_none() {
+        return Err(unknown_document(&document));
+    }
+    request_close_tab(&app_handle, &document);
+    Ok(())
+}
+
+/// Called by the frontend when the user drags the window's tabs into a new order.
+/// `order` has to list exactly the window's documents.
+#[tauri::command]
+async fn reorder_tabs(window: tauri::Window, app_handle: tauri::AppHandle, order: Vec<String>) -> Result<(), AppError> {
+    if !app_handle.state::<Tabs>().reorder(window.label(), &order) {
+        return Err(AppError::invalid_argument(format!("Not the tabs of window {}: {:?}", window.label(), order)));
+    }
+    Ok(())
+}
+
+/// Move a document's tab to `target`, another window, at `index` among its tabs, or
+/// to a new window of its own if no target is given. The document keeps its id, its
+/// recovery journal entry and its unsaved changes; the frontend hands over the
+/// buffer as `content`. The window it leaves closes if that was its last tab.
+#[tauri::command]
+async fn move_tab(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    document: String,
+    content: String,
+    target: Option<String>,
+    index: Option<usize>,
+) -> Result<(), AppError> {
+    let tabs = app_handle.state::<Tabs>();
+    let source = tabs.window_of(&document).ok_or_else(|| unknown_document(&document))?;
+    if target.as_deref() == Some(source.as_str()) {
+        return Err(AppError::invalid_argument(format!("Document {} is already in window {}", document, source)));
+    }
+    let target = match target {
+        Some(label) => {
+            app_handle.get_webview_window(&label)
+                .ok_or_else(|| AppError::invalid_argument(format!("Unknown window: {}", label)))?;
+            label
+        }
+        None => build_document_window(&app_handle)?.0,
+    };
+
+    let known = app_handle.state::<OpenDocuments>().get(&document);
+    let info = DocumentInfo {
+        encoding: known.as_ref().map(|doc| doc.encoding.clone()).unwrap_or_default(),
+        line_endings: line_endings::detect(&content),
+        recovered: false,
+        unsaved: app_handle.state::<DirtyDocuments>().0.lock().unwrap().contains(&document),
+        read_only: known.as_ref().and_then(|doc| doc.read_only),
+        view: app_handle.state::<OpenViews>().get(&document),
+    };
+    let path = known.map(|doc| doc.path);
+    if let Some(path) = &path {
+        app_handle.state::<WatcherService>().unsubscribe(&source, path);
+    }
+
+    tabs.move_to(&document, &target, index);
+    println!("Moved tab {} from window {} to window {}", document, source, target);
+    let open = PendingOpen::Document(path, content, info);
+    deliver_tab(&app_handle, &target, PendingTab { id: document.clone(), open: Some(open) });
+    if let Some(target_window) = app_handle.get_webview_window(&target) {
+        let _ = target_window.set_focus();
+    }
+
+    if tabs.list(&source).tabs.is_empty() {
+        window.close()?;
+    } else {
+        window.emit_to(&source, "tab-closed", &document)?;
+        if let Some(active) = tabs.active(&source) {
+            window.emit_to(&source, "tab-activate", active)?;
+        }
+    }
+    Ok(())
+}
+
+/// Called by the frontend as the user scrolls, moves the cursor, zooms or toggles
+/// reading mode, so the session can bring the document back the same way
+#[tauri::command]
+async fn update_view_state(app_handle: tauri::AppHandle, document: String, view: ViewState) -> Result<(), AppError> {
+    app_handle.state::<OpenViews>().set(&document, view);
     Ok(())
 }
 
+/// Every open document with its window, file, unsaved state, encoding and the hash
+/// it was last loaded or saved with
+#[tauri::command]
+async fn list_documents(app_handle: tauri::AppHandle) -> Result<Vec<DocumentEntry>, AppError> {
+    Ok(document_entries(&app_handle))
+}
+
+/// View state of a document, set when its window was restored from the last session
+#[tauri::command]
+async fn get_view_state(app_handle: tauri::AppHandle, document: String) -> Result<Option<ViewState>, AppError> {
+    Ok(app_handle.state::<OpenViews>().get(&document))
+}
+
 /// Called by the frontend once it has initialized and registered all event listeners.
-/// Marks the window as ready and returns any file that was queued to open
-/// before the frontend was available (e.g. cold-start file double-click).
+/// Marks the window as ready and returns the tabs that were queued before the
+/// frontend was available (e.g. cold-start file double-click), with the one in front.
 #[tauri::command]
-async fn window_ready(window: tauri::Window, app_handle: tauri::AppHandle) -> Result<Option<(String, String)>, String> {
+async fn window_ready(window: tauri::Window, app_handle: tauri::AppHandle) -> Result<ReadyTabs, AppError> {
     let window_label = window.label().to_string();
 
-    // Mark as ready so future file-open events can emit directly
+    // Mark as ready so future tabs can be emitted directly
     {
         let ready_windows: tauri::State<ReadyWindows> = app_handle.state::<ReadyWindows>();
         let mut ready_set = ready_windows.inner().0.lock().unwrap();
@@ -335,48 +1913,186 @@ async fn window_ready(window: tauri::Window, app_handle: tauri::AppHandle) -> Re
         println!("Window {} marked as ready", window_label);
     }
 
-    // Return and clear any pending file
+    // Return and clear the pending tabs
     let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
-    let mut pending = pending_files.inner().0.lock().unwrap();
-    let result = pending.remove(&window_label);
-    if let Some((ref path, _)) = result {
-        println!("Returning pending file to window {}: {}", window_label, path);
-    }
-    Ok(result)
+    let tabs = pending_files.inner().0.lock().unwrap().remove(&window_label).unwrap_or_default();
+    println!("Returning {} pending tab(s) to window {}", tabs.len(), window_label);
+    Ok(ReadyTabs {
+        tabs,
+        active: app_handle.state::<Tabs>().active(&window_label),
+    })
 }
 
+/// "Open…" and "Open Read-Only…": pick files and load the first into the window, the
+/// rest as `open_files` says. A file that is already open in another tab brings that
+/// tab to the front instead. "Open in New Window…" passes `new_window: true` to give
+/// the first file a window of its own.
 #[tauri::command]
-async fn open_file_dialog(window: tauri::WebviewWindow, app_handle: tauri::AppHandle) -> Result<(), String> {
+async fn open_file_dialog(
+    window: tauri::WebviewWindow,
+    app_handle: tauri::AppHandle,
+    read_only: Option<bool>,
+    new_window: Option<bool>,
+) -> Result<(), AppError> {
     use tauri_plugin_dialog::DialogExt;
 
+    let read_only = read_only.unwrap_or(false);
+    let new_window = new_window.unwrap_or(false);
     let dialog = app_handle.dialog().file()
         .add_filter("Markdown files", &["md", "markdown", "txt"])
-        .set_title("Open Markdown File");
+        .set_title(if read_only { "Open Markdown File Read-Only" } else { "Open Markdown File" });
 
-    let window_clone = window.clone();
     let window_label = window.label().to_string();
-    dialog.pick_file(move |path| {
-        if let Some(path) = path {
-            let path_buf = std::path::PathBuf::from(path.as_path().unwrap());
-            let path_str = path_buf.to_string_lossy().to_string();
-            match fs::read_to_string(&path_buf) {
-                Ok(content) => {
-                    let _ = window_clone.emit_to(&window_label, "file-opened", (path_str, content));
-                }
-                Err(e) => {
-                    eprintln!("Error reading file: {}", e);
-                }
+    dialog.pick_files(move |paths| {
+        let paths: Vec<PathBuf> = paths.unwrap_or_default().into_iter()
+            .filter_map(|path| path.into_path().ok())
+            .collect();
+        let target = if new_window { None } else { Some(window_label.as_str()) };
+        open_files(&app_handle, &window_label, target, paths, read_only);
+    });
+
+    Ok(())
+}
+
+/// Open a file the user brought to a particular window, with the Open dialog or by
+/// dropping it there. It replaces the document in the tab in front, or gets a tab of
+/// its own when files open in tabs and the window isn't empty.
+fn open_in_window(app_handle: &tauri::AppHandle, window_label: &str, file: FileToOpen) {
+    // Once it holds a file the window is no longer empty, even before its frontend says so
+    let was_empty = app_handle.state::<EmptyWindows>().lock().unwrap().remove(window_label);
+    let in_new_tab = app_handle.state::<AppSettings>().get().open_files_in == OpenFilesIn::Tab && !was_empty;
+    if in_new_tab {
+        open_tab(app_handle, window_label, Some(file));
+    } else {
+        open_in_front_tab(app_handle, window_label, file);
+    }
+}
+
+/// Load a file into the window's tab in front, replacing its document unless that
+/// has unsaved changes
+fn open_in_front_tab(app_handle: &tauri::AppHandle, window_label: &str, file: FileToOpen) {
+    let front = app_handle.state::<Tabs>().active(window_label);
+    let is_dirty = front.as_ref()
+        .is_some_and(|document| app_handle.state::<DirtyDocuments>().0.lock().unwrap().contains(document));
+    match front {
+        // Never replace unsaved changes: the file gets a tab or window of its own instead
+        Some(_) if is_dirty => {
+            println!("Front tab of window {} has unsaved changes; not replacing it", window_label);
+            if app_handle.state::<AppSettings>().get().open_files_in == OpenFilesIn::Tab {
+                open_tab(app_handle, window_label, Some(file));
+            } else if let Err(e) = create_document_window(app_handle, Some(file)) {
+                emit_file_error(app_handle, window_label, "open", e);
+            }
+        }
+        Some(document) => {
+            // The id now names another document, so nothing of the old one may linger
+            // under it, such as a recovery buffer
+            forget_document(app_handle, &document);
+            let open = pending_open(app_handle, &document, file);
+            deliver_tab(app_handle, window_label, PendingTab { id: document, open: Some(open) });
+        }
+        None => {
+            open_tab(app_handle, window_label, Some(file));
+        }
+    }
+}
+
+/// Open files the user brought to the app together, by dropping them on a window or
+/// picking several in the Open dialog. The first goes to the `target` window as
+/// `open_in_window` decides, or to a new window if there is no target; the rest go
+/// through `handle_file_open`, with tabs beside the first. Errors are reported to
+/// the `origin` window.
+fn open_files(app_handle: &tauri::AppHandle, origin: &str, target: Option<&str>, paths: Vec<PathBuf>, read_only: bool) {
+    let active = target.and_then(|label| app_handle.state::<Tabs>().active(label));
+    let mut near: Option<String> = None;
+    for path in paths {
+        let path_str = path.to_string_lossy().to_string();
+        if near.is_none() && target.is_some() && focus_open_document(app_handle, &path, active.as_deref()) {
+            near = target.map(str::to_string);
+            continue;
+        }
+        let file = match prepare_file_open(app_handle, &path, read_only) {
+            Ok(file) => file,
+            Err(e) => {
+                emit_file_error(app_handle, origin, "open", AppError::io(e, &path));
+                continue;
+            }
+        };
+        if let Some(label) = near.as_deref() {
+            handle_file_open(app_handle, path_str, file, Some(label));
+        } else if let Some(label) = target {
+            println!("Opening {} in window {}", path_str, label);
+            open_in_window(app_handle, label, file);
+            near = Some(label.to_string());
+        } else {
+            println!("Opening {} in a new window", path_str);
+            match create_document_window(app_handle, Some(file)) {
+                Ok(window) => near = Some(window.label().to_string()),
+                Err(e) => emit_file_error(app_handle, origin, "open", e),
             }
         }
+    }
+}
+
+/// Open what was dropped on a window: the first file in it, the rest beside it.
+/// Folders are looked through on a worker thread so a large one doesn't hold up
+/// the window; if they hold more Markdown files than the settings allow, the user
+/// is asked before any of them open.
+fn open_dropped_paths(app_handle: &tauri::AppHandle, window_label: &str, paths: Vec<PathBuf>) {
+    let app = app_handle.clone();
+    let window_label = window_label.to_string();
+    std::thread::spawn(move || {
+        let limit = app.state::<AppSettings>().get().drops.confirm_folder_files_above;
+        // Stop counting past the limit, so the question comes up right away
+        let dropped = dropped::expand(&paths, Some(limit));
+        if dropped.from_folders > limit {
+            confirm_dropped_folders(&app, &window_label, paths, limit);
+            return;
+        }
+        println!("Opening {} dropped file(s), {} from folders", dropped.files.len(), dropped.from_folders);
+        open_files(&app, &window_label, Some(&window_label), dropped.files, false);
     });
+}
 
-    Ok(())
+/// Ask before opening dropped folders holding more than `limit` Markdown files, and
+/// look through all of them on a worker thread if the answer is Open
+fn confirm_dropped_folders(app_handle: &tauri::AppHandle, window_label: &str, paths: Vec<PathBuf>, limit: usize) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let app = app_handle.clone();
+    let window_label = window_label.to_string();
+    let mut dialog = app_handle.dialog()
+        .message(format!("The dropped folders contain more than {} Markdown files. Open all of them?", limit))
+        .title("Open Dropped Folders")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("Open".to_string(), "Cancel".to_string()));
+    if let Some(window) = app_handle.get_webview_window(&window_label) {
+        dialog = dialog.parent(&window);
+    }
+    dialog.show(move |open| {
+        if !open {
+            return;
+        }
+        std::thread::spawn(move || {
+            let dropped = dropped::expand(&paths, None);
+            if dropped.stopped_early {
+                println!("Stopped looking through the dropped folders after {} file(s)", dropped.files.len());
+            }
+            println!("Opening {} dropped file(s), {} from folders", dropped.files.len(), dropped.from_folders);
+            open_files(&app, &window_label, Some(&window_label), dropped.files, false);
+        });
+    });
 }
 
-/// Core file-open logic shared by RunEvent::Opened, application:openFile:, and drag-drop.
-/// Reuses an empty window if one is available; otherwise creates a new document window.
-fn handle_file_open(app: &tauri::AppHandle, path_str: String, content: String) {
+/// Core file-open logic shared by RunEvent::Opened, application:openFile:, the
+/// second-instance callback and files opened together. Reuses an empty window if one
+/// is available; otherwise opens a tab in the `near` window, or the focused one, or a
+/// new window, as the `openFilesIn` setting says.
+fn handle_file_open(app: &tauri::AppHandle, path_str: String, file: FileToOpen, near: Option<&str>) {
     println!("handle_file_open: {}", path_str);
+    if focus_open_document(app, std::path::Path::new(&path_str), None) {
+        return;
+    }
 
     let empty_window_label = {
         let empty_windows: tauri::State<EmptyWindows> = app.state::<EmptyWindows>();
@@ -392,32 +2108,35 @@ fn handle_file_open(app: &tauri::AppHandle, path_str: String, content: String) {
 
     if let Some(window_label) = empty_window_label {
         println!("Reusing empty window {} for file: {}", window_label, path_str);
-        let is_ready = {
-            let ready_windows: tauri::State<ReadyWindows> = app.state::<ReadyWindows>();
-            ready_windows.inner().0.lock().unwrap().contains(&window_label)
-        };
-        if is_ready {
-            if let Some(window) = app.get_webview_window(&window_label) {
-                let wl = window_label.clone();
-                let p = path_str.clone();
-                let c = content.clone();
-                tauri::async_runtime::spawn(async move {
-                    let _ = window.emit_to(&wl, "file-opened", (p, c));
-                });
-            }
-        } else {
-            let pending_files: tauri::State<PendingFiles> = app.state::<PendingFiles>();
-            pending_files.inner().0.lock().unwrap()
-                .insert(window_label.clone(), (path_str, content));
-            println!("Window {} not ready yet; stored as pending file", window_label);
-        }
-    } else {
-        println!("No empty window; creating new window for: {}", path_str);
-        match create_document_window(app, Some(path_str.clone()), Some(content)) {
-            Ok(_) => {},
-            Err(e) => eprintln!("Failed to create window for file {}: {}", path_str, e),
-        }
+        open_in_front_tab(app, &window_label, file);
+        return;
+    }
+
+    let open_files_in = app.state::<AppSettings>().get().open_files_in;
+    let window = near.and_then(|label| app.get_webview_window(label)).or_else(|| get_target_window(app));
+    if let Some(window) = window.filter(|_| open_files_in == OpenFilesIn::Tab) {
+        println!("Opening {} in a new tab of window {}", path_str, window.label());
+        open_tab(app, window.label(), Some(file));
+        let _ = window.set_focus();
+        return;
     }
+
+    println!("No empty window; creating new window for: {}", path_str);
+    match create_document_window(app, Some(file)) {
+        Ok(_) => {},
+        Err(e) => eprintln!("Failed to create window for file {}: {}", path_str, e),
+    }
+}
+
+/// If `path` is already open as a document other than `except`, bring its tab and
+/// window to the front and return true, so one file doesn't end up in two diverging buffers
+fn focus_open_document(app_handle: &tauri::AppHandle, path: &std::path::Path, except: Option<&str>) -> bool {
+    let Some(document) = app_handle.state::<OpenDocuments>().document_showing(path, except) else { return false };
+    if show_tab(app_handle, &document).is_none() {
+        return false;
+    }
+    println!("{} is already open as {}; focusing it", path.display(), document);
+    true
 }
 
 /// Helper function to get the focused window or fall back to any available window
@@ -438,19 +2157,8 @@ fn get_target_window(app: &tauri::AppHandle) -> Option<tauri::WebviewWindow> {
 
 /// Clean up file watchers for a specific window
 fn cleanup_window_watchers(app_handle: &tauri::AppHandle, window_label: &str) {
-    let watchers: FileWatchers = app_handle.state::<FileWatchers>().inner().clone();
-    let mut watchers_lock = watchers.lock().unwrap();
-
-    // Remove all watchers that start with this window's label
-    let keys_to_remove: Vec<String> = watchers_lock
-        .keys()
-        .filter(|k| k.starts_with(&format!("{}:", window_label)))
-        .cloned()
-        .collect();
-
-    for key in keys_to_remove {
-        watchers_lock.remove(&key);
-        println!("Cleaned up file watcher: {}", key);
+    for path in app_handle.state::<WatcherService>().unsubscribe_window(window_label) {
+        println!("Cleaned up file watcher: {} for window: {}", path, window_label);
     }
 }
 
@@ -481,7 +2189,7 @@ fn setup_dock_menu(app_handle: &tauri::AppHandle) {
         if let Some(app) = DOCK_APP_HANDLE.get() {
             let app = app.clone();
             let _ = app.clone().run_on_main_thread(move || {
-                if let Err(e) = create_document_window(&app, None, None) {
+                if let Err(e) = create_document_window(&app, None) {
                     eprintln!("Failed to create window from dock menu: {}", e);
                 }
             });
@@ -504,11 +2212,11 @@ fn setup_dock_menu(app_handle: &tauri::AppHandle) {
         };
         println!("application:openFile: received: {}", path_str);
         if let Some(app) = DOCK_APP_HANDLE.get() {
-            match std::fs::read_to_string(&path_str) {
-                Ok(content) => {
+            match prepare_file_open(app, std::path::Path::new(&path_str), false) {
+                Ok(file) => {
                     let app_clone = app.clone();
                     let _ = app.run_on_main_thread(move || {
-                        handle_file_open(&app_clone, path_str, content);
+                        handle_file_open(&app_clone, path_str, file, None);
                     });
                 }
                 Err(e) => {
@@ -603,31 +2311,39 @@ fn setup_dock_menu(app_handle: &tauri::AppHandle) {
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
-        .manage(FileWatchers::default())
+        .manage(WatcherService::default())
         .manage(EmptyWindows::default())
         .manage(PendingFiles::default())
         .manage(ReadyWindows::default())
+        .manage(DirtyDocuments::default())
+        .manage(PendingCloses::default())
+        .manage(Tabs::default())
+        .manage(OpenDocuments::default())
+        .manage(AppSettings::default())
+        .manage(RecoveryJournal::default())
+        .manage(LocalHistory::default())
+        .manage(ChunkedOpens::default())
+        .manage(OpenViews::default())
+        .manage(DocumentViews::default())
+        .manage(WindowMenu::default())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
             println!("Single instance callback - argv: {:?}, cwd: {:?}", argv, cwd);
 
-            // Look for file arguments in the new instance
-            // Create a NEW window for each file instead of reusing existing
+            // Look for file arguments in the new instance and open each of them,
+            // in a window or a tab as the settings say
+            let read_only = argv.iter().any(|arg| arg == READ_ONLY_FLAG);
             for arg in argv.iter().skip(1) {
                 let path = std::path::Path::new(arg);
-                if path.exists() && (arg.ends_with(".md") || arg.ends_with(".markdown") || arg.ends_with(".txt")) {
+                if path.exists() && dropped::is_markdown_file(path) {
                     println!("Found file to open from second instance: {}", arg);
-
-                    // Read the file and create a new window
-                    if let Ok(content) = fs::read_to_string(arg) {
-                        match create_document_window(app, Some(arg.clone()), Some(content)) {
-                            Ok(_) => println!("Created new window for file: {}", arg),
-                            Err(e) => eprintln!("Failed to create window for file {}: {}", arg, e),
-                        }
+                    match prepare_file_open(app, path, read_only) {
+                        Ok(file) => handle_file_open(app, arg.clone(), file, None),
+                        Err(e) => eprintln!("Failed to read file {}: {}", arg, e),
                     }
-                    // Continue to create windows for all file arguments, not just the first
+                    // Continue to open all file arguments, not just the first
                 }
             }
         }))
@@ -640,6 +2356,16 @@ pub fn run() {
                 )?;
             }
 
+            // Load user settings before any document is opened
+            if let Some(path) = settings_path(app.handle()) {
+                app.state::<AppSettings>().set(Settings::load(&path));
+                println!("Loaded settings from {}", path.display());
+            }
+            if let Some(path) = view_state_path(app.handle()) {
+                let settings = app.state::<AppSettings>().get().view_state;
+                app.state::<DocumentViews>().load(path, &settings);
+            }
+
             // Process command line arguments for first instance startup
             let args: Vec<String> = std::env::args().collect();
             println!("App setup - found {} arguments:", args.len());
@@ -648,17 +2374,18 @@ pub fn run() {
             }
 
             // Check if any file arguments were passed
-            let mut file_to_open: Option<(String, String)> = None;
+            let read_only = args.iter().any(|arg| arg == READ_ONLY_FLAG);
+            let mut file_to_open: Option<FileToOpen> = None;
             for arg in args.iter().skip(1) {
                 let path = std::path::Path::new(arg);
-                let is_markdown = arg.ends_with(".md") || arg.ends_with(".markdown") || arg.ends_with(".txt");
+                let is_markdown = dropped::is_markdown_file(path);
 
                 if path.exists() && is_markdown {
                     println!("Found file to open from first instance: {}", arg);
 
-                    match fs::read_to_string(arg) {
-                        Ok(content) => {
-                            file_to_open = Some((arg.clone(), content));
+                    match prepare_file_open(app.handle(), path, read_only) {
+                        Ok(file) => {
+                            file_to_open = Some(file);
                             break;
                         }
                         Err(e) => {
@@ -668,59 +2395,22 @@ pub fn run() {
                 }
             }
 
-            // Create menu
-            let app_menu = SubmenuBuilder::new(app, "Mark-us-Down")
-                .item(&MenuItemBuilder::new("About Mark-us-Down").id("about").build(app)?)
-                .separator()
-                .item(&MenuItemBuilder::new("Quit Mark-us-Down").id("quit").accelerator("CmdOrCtrl+Q").build(app)?)
-                .build()?;
-
-            let file_menu = SubmenuBuilder::new(app, "File")
-                .item(&MenuItemBuilder::new("New Window").id("new_window").accelerator("CmdOrCtrl+Shift+N").build(app)?)
-                .item(&MenuItemBuilder::new("New").id("new").accelerator("CmdOrCtrl+N").build(app)?)
-                .item(&MenuItemBuilder::new("Open...").id("open").accelerator("CmdOrCtrl+O").build(app)?)
-                .separator()
-                .item(&MenuItemBuilder::new("Save").id("save").accelerator("CmdOrCtrl+S").build(app)?)
-                .item(&MenuItemBuilder::new("Save As...").id("save_as").accelerator("CmdOrCtrl+Shift+S").build(app)?)
-                .separator()
-                .item(&MenuItemBuilder::new("Print...").id("print").accelerator("CmdOrCtrl+P").build(app)?)
-                .separator()
-                .item(&MenuItemBuilder::new("Close").id("close").accelerator("CmdOrCtrl+W").build(app)?)
-                .build()?;
-
-            let edit_menu = SubmenuBuilder::new(app, "Edit")
-                .item(&MenuItemBuilder::new("Undo").id("undo").accelerator("CmdOrCtrl+Z").build(app)?)
-                .item(&MenuItemBuilder::new("Redo").id("redo").accelerator("CmdOrCtrl+Shift+Z").build(app)?)
-                .separator()
-                .item(&PredefinedMenuItem::cut(app, None)?)
-                .item(&PredefinedMenuItem::copy(app, None)?)
-                .item(&PredefinedMenuItem::paste(app, None)?)
-                .separator()
-                .item(&PredefinedMenuItem::select_all(app, None)?)
-                .build()?;
-
-            let view_menu_builder = SubmenuBuilder::new(app, "View")
-                .item(&MenuItemBuilder::new("Switch to Dark Mode").id("theme_toggle").accelerator("CmdOrCtrl+T").build(app)?)
-                .item(&MenuItemBuilder::new("Toggle Reading Mode").id("reading_mode").accelerator("CmdOrCtrl+E").build(app)?)
-                .separator()
-                .item(&MenuItemBuilder::new("Zoom In").id("zoom_in").accelerator("CmdOrCtrl+Plus").build(app)?)
-                .item(&MenuItemBuilder::new("Zoom Out").id("zoom_out").accelerator("CmdOrCtrl+-").build(app)?)
-                .item(&MenuItemBuilder::new("Reset Zoom").id("reset_zoom").accelerator("CmdOrCtrl+0").build(app)?);
-
-            #[cfg(debug_assertions)]
-            let view_menu_builder = view_menu_builder
-                .separator()
-                .item(&MenuItemBuilder::new("Debug Info").id("debug_info").build(app)?);
-
-            let view_menu = view_menu_builder.build()?;
-
-            let menu = MenuBuilder::new(app)
-                .item(&app_menu)
-                .item(&file_menu)
-                .item(&edit_menu)
-                .item(&view_menu)
-                .build()?;
+            // Start journaling unsaved buffers for crash recovery
+            if let Some(dir) = recovery_dir(app.handle()) {
+                app.state::<RecoveryJournal>().set_dir(dir);
+            }
+            app.state::<RecoveryJournal>().spawn_flusher();
+
+            let watch_handle = app.handle().clone();
+            app.state::<WatcherService>().spawn(move |notices| handle_watch_notices(&watch_handle, notices));
+
+            if let Some(dir) = history_dir(app.handle()) {
+                app.state::<LocalHistory>().set_dir(dir);
+                app.state::<LocalHistory>().spawn_garbage_collection(app.state::<AppSettings>().get().history);
+            }
 
+            // Create menu
+            let menu = build_menu(app.handle(), false)?;
             app.set_menu(menu)?;
 
             // Set up the macOS dock right-click menu
@@ -732,9 +2422,9 @@ pub fn run() {
             // so that application:openFile: (macOS Finder double-click) has a chance to
             // fire first — preventing a stale empty window from being created alongside
             // the file window that application:openFile: opens.
-            if let Some((file_path, content)) = file_to_open {
+            if let Some(document) = file_to_open {
                 // Launched from the command line with a file argument
-                if let Err(e) = create_document_window(app.handle(), Some(file_path), Some(content)) {
+                if let Err(e) = create_document_window(app.handle(), Some(document)) {
                     eprintln!("Failed to create window for file: {}", e);
                 }
                 FILE_OPEN_HANDLED.store(true, Ordering::SeqCst);
@@ -750,24 +2440,76 @@ pub fn run() {
             save_file_dialog,
             save_file,
             read_file,
+            open_file_chunked,
+            cancel_file_open,
+            save_with_encoding,
+            reopen_with_encoding,
+            list_encodings,
+            acknowledge_external_change,
+            merge_external_change,
+            convert_line_endings,
+            get_settings,
+            update_settings,
+            list_backups,
+            restore_backup,
+            list_history,
+            diff_snapshots,
+            diff_snapshot_with_buffer,
+            restore_snapshot,
+            label_snapshot,
+            open_as_editable_copy,
+            update_recovery_buffer,
+            discard_recovery_buffer,
             open_file_dialog,
             update_theme_menu,
             debug_args,
+            debug_watchers,
+            watch_assets,
             start_file_watcher,
             stop_file_watcher,
             set_window_empty,
+            set_document_dirty,
+            finish_save_before_close,
+            new_tab,
+            activate_tab,
+            close_tab,
+            reorder_tabs,
+            move_tab,
+            update_view_state,
+            get_view_state,
+            list_documents,
             window_ready
         ])
         .on_menu_event(handle_menu_event)
         .on_window_event(|window, event| match event {
-            WindowEvent::CloseRequested { .. } => {
+            WindowEvent::CloseRequested { api, .. } => {
                 let window_label = window.label().to_string();
                 println!("Window close requested: {}", window_label);
 
                 let app_handle = window.app_handle();
 
+                // A window with unsaved changes stays open until the user has decided what
+                // happens to them, one document at a time; it is closed again once each
+                // is saved or discarded
+                let documents = app_handle.state::<Tabs>().list(&window_label).tabs;
+                let first_dirty = {
+                    let dirty = app_handle.state::<DirtyDocuments>();
+                    let dirty = dirty.0.lock().unwrap();
+                    documents.into_iter().find(|document| dirty.contains(document))
+                };
+                if let Some(document) = first_dirty {
+                    api.prevent_close();
+                    confirm_unsaved_changes(app_handle, &document, CloseAction::Window);
+                    return;
+                }
+
+                // Keep the session current. The last window stays in it, so the next
+                // launch brings back what was open when the app went away.
+                let closing = (app_handle.webview_windows().len() > 1).then_some(window_label.as_str());
+                save_session(app_handle, closing);
+
                 // Clean up file watchers for this window
-                cleanup_window_watchers(&app_handle, &window_label);
+                cleanup_window_watchers(app_handle, &window_label);
 
                 // Remove from empty windows tracking
                 {
@@ -776,6 +2518,12 @@ pub fn run() {
                     empty_set.remove(&window_label);
                 }
 
+                // Forget the documents in the window's tabs
+                for document in app_handle.state::<Tabs>().remove_window(&window_label) {
+                    forget_document(app_handle, &document);
+                }
+                app_handle.state::<ChunkedOpens>().cancel(&window_label);
+
                 // Remove from ready/pending tracking
                 {
                     let ready_windows: tauri::State<ReadyWindows> = app_handle.state::<ReadyWindows>();
@@ -785,6 +2533,7 @@ pub fn run() {
                     let pending_files: tauri::State<PendingFiles> = app_handle.state::<PendingFiles>();
                     pending_files.inner().0.lock().unwrap().remove(&window_label);
                 }
+                refresh_window_menu(app_handle);
 
                 // Count remaining windows
                 let windows = app_handle.webview_windows();
@@ -812,31 +2561,23 @@ pub fn run() {
                     // Let the window close naturally
                 }
             }
+            WindowEvent::Focused(true) => {
+                // Move the Window menu's checkmark to this window's document
+                let app_handle = window.app_handle().clone();
+                let label = window.label().to_string();
+                let _ = window.app_handle().run_on_main_thread(move || {
+                    if let Some(menu) = app_handle.state::<WindowMenu>().0.lock().unwrap().as_mut() {
+                        menu.focused = Some(label);
+                    }
+                    if let Err(e) = mark_window_menu_documents(&app_handle) {
+                        eprintln!("Failed to update the Window menu: {}", e);
+                    }
+                });
+            }
             WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
                 println!("Drag drop event received with {} files in window: {}", paths.len(), window.label());
-                let window_label = window.label().to_string();
-                // Handle dropped files - open in THIS window (not create new ones)
-                for path in paths {
-                    println!("Processing dropped file: {:?}", path);
-                    if let Some(extension) = path.extension() {
-                        if extension == "md" || extension == "markdown" || extension == "txt" {
-                            match fs::read_to_string(&path) {
-                                Ok(content) => {
-                                    println!("Opening dropped file in window: {}", window_label);
-                                    match window.emit_to(&window_label, "file-opened", (path.to_string_lossy().to_string(), content)) {
-                                        Ok(_) => println!("Successfully emitted file-opened event to {}", window_label),
-                                        Err(e) => println!("Failed to emit file-opened event: {}", e),
-                                    }
-                                    break; // Only open the first markdown/text file
-                                }
-                                Err(e) => {
-                                    eprintln!("Error reading file {:?}: {}", path, e);
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
+                // The first dropped file opens in THIS window, the rest beside it
+                open_dropped_paths(window.app_handle(), window.label(), paths.clone());
             }
             _ => {}
         })
@@ -853,10 +2594,10 @@ pub fn run() {
                     for url in urls {
                         let path = url.to_file_path().unwrap_or_else(|_| std::path::PathBuf::from(url.as_str()));
                         let path_str = path.to_string_lossy().to_string();
-                        if path.exists() && (path_str.ends_with(".md") || path_str.ends_with(".markdown") || path_str.ends_with(".txt")) {
-                            match fs::read_to_string(&path) {
-                                Ok(content) => {
-                                    handle_file_open(app_handle, path_str, content);
+                        if path.exists() && dropped::is_markdown_file(&path) {
+                            match prepare_file_open(app_handle, &path, false) {
+                                Ok(file) => {
+                                    handle_file_open(app_handle, path_str, file, None);
                                     FILE_OPEN_HANDLED.store(true, Ordering::SeqCst);
                                 }
                                 Err(e) => eprintln!("Error reading opened file {}: {}", path_str, e),
@@ -870,7 +2611,7 @@ pub fn run() {
                     println!("Reopen event - has_visible_windows: {}", has_visible_windows);
                     if !has_visible_windows {
                         // Create a new empty window when clicking dock icon with no windows
-                        match create_document_window(app_handle, None, None) {
+                        match create_document_window(app_handle, None) {
                             Ok(_) => println!("Created new window on reopen"),
                             Err(e) => eprintln!("Failed to create window on reopen: {}", e),
                         }
@@ -879,13 +2620,49 @@ pub fn run() {
                 // Primary path for normal (no-file) launches: setup() defers empty
                 // window creation here so that application:openFile: (Finder cold-start)
                 // can fire first. If a file was already opened, this is a no-op.
+                // Buffers recovered from a crashed session are offered first. If nothing
+                // else ends up open, the last session is restored, or failing that the
+                // fallback window is created.
                 tauri::RunEvent::Ready => {
-                    let windows = app_handle.webview_windows();
-                    if windows.is_empty() && !FILE_OPEN_HANDLED.load(Ordering::SeqCst) {
-                        println!("RunEvent::Ready: no window found, creating fallback window");
-                        if let Err(e) = create_document_window(app_handle, None, None) {
-                            eprintln!("Failed to create fallback window: {}", e);
+                    let create_fallback = |app_handle: &tauri::AppHandle| {
+                        let windows = app_handle.webview_windows();
+                        if windows.is_empty() && !FILE_OPEN_HANDLED.load(Ordering::SeqCst) {
+                            // Bring back the last session instead, unless it's turned off or empty
+                            if restore_session(app_handle) > 0 {
+                                return;
+                            }
+                            println!("RunEvent::Ready: no window found, creating fallback window");
+                            if let Err(e) = create_document_window(app_handle, None) {
+                                eprintln!("Failed to create fallback window: {}", e);
+                            }
                         }
+                    };
+                    let recovered = app_handle.state::<RecoveryJournal>().leftover_entries();
+                    if recovered.is_empty() {
+                        create_fallback(app_handle);
+                    } else {
+                        println!("RunEvent::Ready: found {} recovered documents", recovered.len());
+                        offer_recovery(app_handle, recovered, create_fallback);
+                    }
+                }
+                // Quitting from the dock, the app switcher or an OS logout bypasses the
+                // Quit menu item, so ask about unsaved changes here too
+                tauri::RunEvent::ExitRequested { api, .. } => {
+                    if has_unsaved_documents(app_handle) {
+                        api.prevent_exit();
+                        if !QUIT_PENDING.swap(true, Ordering::SeqCst) {
+                            continue_quit(app_handle);
+                        }
+                    }
+                }
+                // Quitting discards unsaved buffers, so a clean exit leaves no journal behind.
+                // An exit that went ahead with unsaved changes anyway keeps them recoverable.
+                tauri::RunEvent::Exit => {
+                    let journal = app_handle.state::<RecoveryJournal>();
+                    if !has_unsaved_documents(app_handle) {
+                        journal.clear_session();
+                    } else {
+                        journal.flush_due(std::time::Duration::ZERO);
                     }
                 }
                 _ => {}
@@ -903,11 +2680,21 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
         "new_window" => {
             // Create a new empty window
             println!("Creating new window from menu");
-            match create_document_window(app, None, None) {
+            match create_document_window(app, None) {
                 Ok(_) => println!("Created new window from menu"),
                 Err(e) => eprintln!("Failed to create new window: {}", e),
             }
         }
+        "new_tab" => {
+            match target_window {
+                Some(window) => {
+                    open_tab(app, window.label(), None);
+                }
+                None => {
+                    let _ = create_document_window(app, None);
+                }
+            }
+        }
         "new" => {
             // Handle new file in current window
             println!("Handling new file menu");
@@ -918,16 +2705,18 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
                 }
             } else {
                 // No windows open, create a new one
-                let _ = create_document_window(app, None, None);
+                let _ = create_document_window(app, None);
             }
         }
-        "open" => {
+        "open" | "open_read_only" | "open_new_window" => {
             println!("Handling open file menu");
+            let read_only = event.id().as_ref() == "open_read_only";
+            let new_window = event.id().as_ref() == "open_new_window";
             if let Some(window) = target_window {
                 let app_handle = app.clone();
                 let window_clone = window.clone();
                 tauri::async_runtime::spawn(async move {
-                    match open_file_dialog(window_clone, app_handle).await {
+                    match open_file_dialog(window_clone, app_handle, Some(read_only), Some(new_window)).await {
                         Ok(_) => println!("File dialog opened successfully"),
                         Err(e) => println!("Failed to open file dialog: {}", e),
                     }
@@ -944,6 +2733,41 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
                 let _ = window.emit_to(window.label(), "menu-save-as-file", ());
             }
         }
+        "reopen_with_encoding" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-reopen-with-encoding", ());
+            }
+        }
+        "save_with_encoding" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-save-with-encoding", ());
+            }
+        }
+        "restore_backup" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-restore-backup", ());
+            }
+        }
+        "local_history" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-local-history", ());
+            }
+        }
+        "label_checkpoint" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-label-checkpoint", ());
+            }
+        }
+        "convert_eol_lf" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-convert-line-endings", LineEnding::Lf);
+            }
+        }
+        "convert_eol_crlf" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-convert-line-endings", LineEnding::Crlf);
+            }
+        }
         "print" => {
             if let Some(window) = target_window {
                 match window.print() {
@@ -953,12 +2777,65 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
             }
         }
         "close" => {
+            if let Some(document) = target_window.and_then(|window| app.state::<Tabs>().active(window.label())) {
+                request_close_tab(app, &document);
+            }
+        }
+        "close_window" => {
             if let Some(window) = target_window {
                 let _ = window.close();
             }
         }
+        "move_tab_new_window" => {
+            if let Some(window) = target_window {
+                let _ = window.emit_to(window.label(), "menu-move-tab", ());
+            }
+        }
+        "next_tab" | "previous_tab" => {
+            let Some(window) = target_window else { return };
+            let list = app.state::<Tabs>().list(window.label());
+            let current = list.active.and_then(|active| list.tabs.iter().position(|tab| *tab == active));
+            if let Some(current) = current {
+                let step = if event.id().as_ref() == "next_tab" { 1 } else { list.tabs.len() - 1 };
+                show_tab(app, &list.tabs[(current + step) % list.tabs.len()]);
+            }
+        }
+        "minimize" => {
+            if let Some(window) = target_window {
+                let _ = window.minimize();
+            }
+        }
+        "zoom" => {
+            if let Some(window) = target_window {
+                let _ = if window.is_maximized().unwrap_or(false) { window.unmaximize() } else { window.maximize() };
+            }
+        }
+        "cycle_windows" => {
+            // Focus the next window in the order they were opened
+            let mut windows: Vec<tauri::WebviewWindow> = app.webview_windows().into_values().collect();
+            windows.sort_by_key(|window| window_number(window.label()));
+            let current = target_window.and_then(|target| windows.iter().position(|window| window.label() == target.label()));
+            if let Some(next) = current.map(|current| &windows[(current + 1) % windows.len()]) {
+                let _ = next.unminimize();
+                let _ = next.show();
+                let _ = next.set_focus();
+            }
+        }
+        "bring_all_to_front" => {
+            for window in app.webview_windows().values() {
+                let _ = window.unminimize();
+                let _ = window.show();
+            }
+            // Keep the window that was in front there
+            if let Some(window) = target_window {
+                let _ = window.set_focus();
+            }
+        }
         "quit" => {
-            app.exit(0);
+            // A second Quit while the first is still asking about unsaved changes does nothing
+            if !QUIT_PENDING.swap(true, Ordering::SeqCst) {
+                continue_quit(app);
+            }
         }
         "undo" => {
             println!("Undo requested");
@@ -1012,6 +2889,12 @@ fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
                 let _ = window.emit_to(window.label(), "menu-debug-info", ());
             }
         }
-        _ => {}
+        id => {
+            if let Some(document) = id.strip_prefix(DOCUMENT_MENU_PREFIX) {
+                // Refreshes the menu, which also puts the checkmark back if this
+                // document was already the one in front
+                show_tab(app, document);
+            }
+        }
     }
 }
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+/// A line terminator style
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The native line ending of the platform the app runs on
+    pub fn os_default() -> Self {
+        if cfg!(windows) { LineEnding::Crlf } else { LineEnding::Lf }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// How line endings are written on save
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LineEndingPolicy {
+    /// Keep whatever the document used when it was opened
+    #[default]
+    Preserve,
+    Lf,
+    Crlf,
+    OsDefault,
+}
+
+impl LineEndingPolicy {
+    /// The line ending to write for a document whose own style is `document`
+    pub fn resolve(self, document: LineEnding) -> LineEnding {
+        match self {
+            LineEndingPolicy::Preserve => document,
+            LineEndingPolicy::Lf => LineEnding::Lf,
+            LineEndingPolicy::Crlf => LineEnding::Crlf,
+            LineEndingPolicy::OsDefault => LineEnding::os_default(),
+        }
+    }
+}
+
+/// Line ending statistics for a document, reported to the frontend on load
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LineEndingInfo {
+    pub dominant: LineEnding,
+    pub mixed: bool,
+    pub lf_count: usize,
+    pub crlf_count: usize,
+}
+
+/// Counts LF and CRLF terminators across text fed in one or more pieces,
+/// so documents loaded in chunks get the same statistics as whole ones
+#[derive(Debug, Default)]
+pub struct LineEndingCounter {
+    lf_count: usize,
+    crlf_count: usize,
+    after_cr: bool,
+}
+
+impl LineEndingCounter {
+    pub fn feed(&mut self, text: &str) {
+        for b in text.bytes() {
+            if b == b'\n' {
+                if self.after_cr {
+                    self.crlf_count += 1;
+                } else {
+                    self.lf_count += 1;
+                }
+            }
+            self.after_cr = b == b'\r';
+        }
+    }
+
+    /// Pick the dominant ending. Text without any line breaks gets the OS default.
+    pub fn finish(&self) -> LineEndingInfo {
+        let dominant = match (self.lf_count, self.crlf_count) {
+            (0, 0) => LineEnding::os_default(),
+            (lf, crlf) if crlf > lf => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        };
+        LineEndingInfo {
+            dominant,
+            mixed: self.lf_count > 0 && self.crlf_count > 0,
+            lf_count: self.lf_count,
+            crlf_count: self.crlf_count,
+        }
+    }
+}
+
+/// Count LF and CRLF terminators and pick the dominant one.
+/// Text without any line breaks gets the OS default.
+pub fn detect(text: &str) -> LineEndingInfo {
+    let mut counter = LineEndingCounter::default();
+    counter.feed(text);
+    counter.finish()
+}
+
+/// Rewrite every line break in `text` (LF, CRLF or a lone CR) as `ending`
+pub fn normalize(text: &str, ending: LineEnding) -> String {
+    let target = ending.as_str();
+    let mut out = String::with_capacity(text.len() + text.len() / 32);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                out.push_str(target);
+            }
+            '\n' => out.push_str(target),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_mixed_endings() {
+        let info = detect("one\r\ntwo\nthree\r\nfour\r\nfive");
+        assert_eq!(info.crlf_count, 3);
+        assert_eq!(info.lf_count, 1);
+        assert!(info.mixed);
+        assert_eq!(info.dominant, LineEnding::Crlf);
+    }
+
+    #[test]
+    fn ties_go_to_lf() {
+        let info = detect("one\r\ntwo\n");
+        assert_eq!((info.lf_count, info.crlf_count), (1, 1));
+        assert_eq!(info.dominant, LineEnding::Lf);
+    }
+
+    #[test]
+    fn counts_crlf_split_across_chunks() {
+        let mut counter = LineEndingCounter::default();
+        counter.feed("one\r");
+        counter.feed("\ntwo\n");
+        let info = counter.finish();
+        assert_eq!((info.lf_count, info.crlf_count), (1, 1));
+    }
+
+    #[test]
+    fn normalizes_every_break() {
+        assert_eq!(normalize("a\r\nb\rc\nd", LineEnding::Lf), "a\nb\nc\nd");
+        assert_eq!(normalize("a\r\nb\rc\nd", LineEnding::Crlf), "a\r\nb\r\nc\r\nd");
+    }
+}
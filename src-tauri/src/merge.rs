@@ -0,0 +1,147 @@
+use diffy::{ConflictStyle, MergeOptions};
+use serde::Serialize;
+
+use crate::line_endings::{self, LineEnding};
+
+// Marker lines diffy writes around a conflict: the user's lines come first,
+// then the disk's
+const OURS_MARKER: &str = "<<<<<<< ours";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>> theirs";
+
+/// Lines of one conflict in the merged text, 1-based like editor line numbers.
+/// The user's version sits between `start_line` and `separator_line`, the disk
+/// version between `separator_line` and `end_line`; all three are marker lines.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    pub start_line: usize,
+    pub separator_line: usize,
+    pub end_line: usize,
+}
+
+/// Result of merging an external change into the editor buffer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeOutcome {
+    pub content: String,
+    /// Empty when every change merged cleanly
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Three-way line merge of the user's edits (`ours`) and the new disk content
+/// (`theirs`), both derived from `base`. Changes to different lines are combined;
+/// overlapping changes are kept side by side between conflict markers. The result
+/// uses the buffer's line endings.
+pub fn merge_texts(base: &str, ours: &str, theirs: &str) -> MergeOutcome {
+    // Compare lines regardless of how each version ends them, or a file rewritten
+    // with CRLF would conflict on every line
+    let buffer_ending = line_endings::detect(ours).dominant;
+    let base = line_endings::normalize(base, LineEnding::Lf);
+    let ours = line_endings::normalize(ours, LineEnding::Lf);
+    let theirs = line_endings::normalize(theirs, LineEnding::Lf);
+
+    let merged = MergeOptions::new()
+        .set_conflict_style(ConflictStyle::Merge)
+        .merge(&base, &ours, &theirs);
+    let (content, conflicts) = match merged {
+        Ok(content) => (content, Vec::new()),
+        Err(content) => {
+            let conflicts = find_conflicts(&content, &ours, &theirs);
+            (content, conflicts)
+        }
+    };
+    let content = match buffer_ending {
+        LineEnding::Lf => content,
+        ending => line_endings::normalize(&content, ending),
+    };
+    MergeOutcome { content, conflicts }
+}
+
+/// Locate the marker lines diffy wrote into `merged`. A `=======` line can also
+/// be a setext heading underline on either side, so the separator is the first
+/// one that splits the conflict into lines taken from `ours` and from `theirs`.
+fn find_conflicts(merged: &str, ours: &str, theirs: &str) -> Vec<MergeConflict> {
+    let lines: Vec<&str> = merged.lines().collect();
+    let ours: Vec<&str> = ours.lines().collect();
+    let theirs: Vec<&str> = theirs.lines().collect();
+    let mut conflicts = Vec::new();
+    let mut start = None;
+    let mut separators = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        match *line {
+            OURS_MARKER => {
+                start = Some(index);
+                separators.clear();
+            }
+            SEPARATOR_MARKER if start.is_some() => separators.push(index),
+            THEIRS_MARKER => {
+                let Some(start_index) = start.take() else { continue };
+                let separator = separators.iter()
+                    .find(|&&separator| {
+                        contains_lines(&ours, &lines[start_index + 1..separator])
+                            && contains_lines(&theirs, &lines[separator + 1..index])
+                    })
+                    .or(separators.first());
+                if let Some(separator) = separator {
+                    conflicts.push(MergeConflict {
+                        start_line: start_index + 1,
+                        separator_line: separator + 1,
+                        end_line: index + 1,
+                    });
+                }
+                separators.clear();
+            }
+            _ => {}
+        }
+    }
+    conflicts
+}
+
+/// Whether `block` appears in `lines` as consecutive lines
+fn contains_lines(lines: &[&str], block: &[&str]) -> bool {
+    block.is_empty() || lines.windows(block.len()).any(|window| window == block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_changes_to_different_lines() {
+        let outcome = merge_texts("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n");
+        assert_eq!(outcome.content, "A\nb\nC\n");
+        assert!(outcome.conflicts.is_empty());
+    }
+
+    #[test]
+    fn reports_conflict_marker_lines() {
+        let outcome = merge_texts("a\nb\nc\nd\ne\n", "a\nb\nours\nd\ne\n", "a\nb\ntheirs\nd\ne\n");
+        let lines: Vec<&str> = outcome.content.lines().collect();
+        assert_eq!(outcome.conflicts, vec![MergeConflict { start_line: 3, separator_line: 5, end_line: 7 }]);
+        assert_eq!(lines[2], OURS_MARKER);
+        assert_eq!(lines[3], "ours");
+        assert_eq!(lines[4], SEPARATOR_MARKER);
+        assert_eq!(lines[5], "theirs");
+        assert_eq!(lines[6], THEIRS_MARKER);
+    }
+
+    #[test]
+    fn setext_heading_in_a_conflict_is_not_the_separator() {
+        let outcome = merge_texts("a\nb\nc\n", "a\nours\nc\n", "a\nTitle\n=======\nc\n");
+        let lines: Vec<&str> = outcome.content.lines().collect();
+        assert_eq!(outcome.conflicts, vec![MergeConflict { start_line: 2, separator_line: 4, end_line: 7 }]);
+        assert_eq!(lines[3], SEPARATOR_MARKER);
+        assert_eq!(&lines[4..6], ["Title", "======="]);
+
+        let outcome = merge_texts("a\nb\nc\n", "a\nTitle\n=======\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(outcome.conflicts, vec![MergeConflict { start_line: 2, separator_line: 5, end_line: 7 }]);
+    }
+
+    #[test]
+    fn keeps_the_buffer_line_endings() {
+        let outcome = merge_texts("a\nb\nc\n", "A\r\nb\r\nc\r\n", "a\nb\nC\n");
+        assert_eq!(outcome.content, "A\r\nb\r\nC\r\n");
+        assert!(outcome.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+// Filesystem types whose changes don't reliably produce native notifications:
+// network filesystems, where edits made on other machines never reach the local
+// kernel, and `fuse.*` mounts such as sshfs or sync clients, whose drivers don't
+// forward them. Local FUSE block devices (`fuseblk`, e.g. NTFS) are fine.
+const REMOTE_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb", "smbfs", "smb3", "afpfs", "webdav", "davfs",
+    "9p", "afs", "ceph", "glusterfs", "lustre", "vboxsf", "prl_fs", "fuse",
+];
+
+/// Whether native change notification can't be trusted on a filesystem type
+pub fn is_remote_filesystem(fs_type: &str) -> bool {
+    let base = fs_type.split('.').next().unwrap_or(fs_type);
+    REMOTE_FILESYSTEMS.contains(&base)
+}
+
+/// Type of the filesystem `path` lives on, e.g. "ext4", "nfs4" or "fuse.sshfs".
+/// `path` should be canonical. Returns `None` if it can't be determined.
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo.lines()
+        .filter_map(parse_mountinfo_line)
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Mount point and filesystem type from a line of /proc/self/mountinfo:
+/// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`
+#[cfg(target_os = "linux")]
+fn parse_mountinfo_line(line: &str) -> Option<(String, String)> {
+    let (fields, rest) = line.split_once(" - ")?;
+    let mount_point = fields.split(' ').nth(4)?;
+    let fs_type = rest.split(' ').next()?;
+    Some((unescape_octal(mount_point), fs_type.to_string()))
+}
+
+/// Undo the `\040`-style escaping of spaces and other characters in mount points
+#[cfg(target_os = "linux")]
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Type of the filesystem `path` lives on, from the output of `mount`:
+/// `//user@server/share on /Volumes/share (smbfs, nodev, nosuid, mounted by user)`
+#[cfg(target_os = "macos")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let output = std::process::Command::new("/sbin/mount").output().ok()?;
+    let mounts = String::from_utf8_lossy(&output.stdout);
+    mounts.lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            let fs_type = options.split([',', ')']).next()?.trim();
+            Some((mount_point.to_string(), fs_type.to_string()))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Windows only tells UNC paths apart here; canonical paths to network shares
+/// look like `\\?\UNC\server\share\…`. Mapped drive letters aren't recognised.
+#[cfg(windows)]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+    (path.starts_with(r"\\?\UNC\") || (path.starts_with(r"\\") && !path.starts_with(r"\\?\")))
+        .then(|| "smb".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::documents::Fingerprint;
+use crate::encoding::TextEncoding;
+use crate::file_io;
+use crate::line_endings::LineEnding;
+
+// Directory inside the app data directory that holds journal entries
+pub const RECOVERY_DIR: &str = "recovery";
+
+// A buffer is written to the journal once it has been left alone for this long
+pub const FLUSH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+// How often the background flusher looks for buffers that are due
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Disk state of the file an unsaved buffer belongs to, so conflict detection
+/// still works after the buffer is restored in a new session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalDocument {
+    pub fingerprint: Fingerprint,
+    pub encoding: TextEncoding,
+    pub line_ending: LineEnding,
+}
+
+/// One unsaved buffer, stored as `<id>.json` in the recovery directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: String,
+    /// `None` for untitled documents
+    pub path: Option<String>,
+    pub content: String,
+    pub updated_ms: u64,
+    pub document: Option<JournalDocument>,
+}
+
+struct BufferedEntry {
+    entry: JournalEntry,
+    changed_at: Instant,
+    flushed: bool,
+}
+
+/// Journal of unsaved buffers, keyed by document id. Updates are kept in memory
+/// and written out by a background thread once they settle, so typing never
+/// waits on disk I/O.
+#[derive(Clone)]
+pub struct RecoveryJournal {
+    session: String,
+    dir: Arc<Mutex<Option<PathBuf>>>,
+    buffers: Arc<Mutex<HashMap<String, BufferedEntry>>>,
+    // Held while entry files are written or deleted, so a buffer discarded during
+    // a flush can't have its file written after it was deleted
+    files: Arc<Mutex<()>>,
+}
+
+impl Default for RecoveryJournal {
+    fn default() -> Self {
+        // Document ids restart at tab-0 every launch, so entries are prefixed
+        // with a per-session id to keep them apart from a crashed session's
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        RecoveryJournal {
+            session: format!("{}-{}", started.as_millis(), std::process::id()),
+            dir: Arc::new(Mutex::new(None)),
+            buffers: Arc::new(Mutex::new(HashMap::new())),
+            files: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+impl RecoveryJournal {
+    pub fn set_dir(&self, dir: PathBuf) {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Failed to create recovery directory {}: {}", dir.display(), e);
+        }
+        *self.dir.lock().unwrap() = Some(dir);
+    }
+
+    fn entry_file(&self, id: &str) -> Option<PathBuf> {
+        self.dir.lock().unwrap().as_ref().map(|dir| dir.join(format!("{}.json", id)))
+    }
+
+    /// Record the latest unsaved content of a document's buffer
+    pub fn update(&self, document_id: &str, path: Option<String>, content: String, document: Option<JournalDocument>) {
+        let entry = JournalEntry {
+            id: format!("{}-{}", self.session, document_id),
+            path,
+            content,
+            updated_ms: now_ms(),
+            document,
+        };
+        self.buffers.lock().unwrap().insert(document_id.to_string(), BufferedEntry {
+            entry,
+            changed_at: Instant::now(),
+            flushed: false,
+        });
+    }
+
+    /// Forget a document's buffer, e.g. after it was saved or deliberately thrown away
+    pub fn discard(&self, document_id: &str) {
+        let _files = self.files.lock().unwrap();
+        let removed = self.buffers.lock().unwrap().remove(document_id);
+        if let Some(buffered) = removed {
+            self.remove_entry(&buffered.entry.id);
+        }
+    }
+
+    /// Delete a journal entry file by id
+    pub fn remove_entry(&self, id: &str) {
+        if let Some(file) = self.entry_file(id) {
+            match fs::remove_file(&file) {
+                Ok(_) => println!("Removed recovery entry {}", id),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("Failed to remove recovery entry {}: {}", file.display(), e),
+            }
+        }
+    }
+
+    /// Write every buffer that changed at least `debounce` ago and hasn't been written yet
+    pub fn flush_due(&self, debounce: Duration) {
+        let _files = self.files.lock().unwrap();
+        let due: Vec<JournalEntry> = {
+            let mut buffers = self.buffers.lock().unwrap();
+            buffers.values_mut()
+                .filter(|b| !b.flushed && b.changed_at.elapsed() >= debounce)
+                .map(|b| {
+                    b.flushed = true;
+                    b.entry.clone()
+                })
+                .collect()
+        };
+        for entry in due {
+            let Some(file) = self.entry_file(&entry.id) else { continue };
+            let json = serde_json::to_vec(&entry).expect("journal entries are always serializable");
+            if let Err(e) = file_io::atomic_write(&file, &json) {
+                eprintln!("Failed to write recovery entry: {}", e);
+            }
+        }
+    }
+
+    /// Drop all of this session's entries, on a clean exit
+    pub fn clear_session(&self) {
+        let documents: Vec<String> = self.buffers.lock().unwrap().keys().cloned().collect();
+        for document in documents {
+            self.discard(&document);
+        }
+    }
+
+    /// Entries left behind by earlier sessions that did not exit cleanly, oldest first
+    pub fn leftover_entries(&self) -> Vec<JournalEntry> {
+        let Some(dir) = self.dir.lock().unwrap().clone() else { return Vec::new() };
+        let Ok(read_dir) = fs::read_dir(&dir) else { return Vec::new() };
+        let mut entries: Vec<JournalEntry> = read_dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| {
+                let json = fs::read_to_string(&p).ok()?;
+                match serde_json::from_str::<JournalEntry>(&json) {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        eprintln!("Skipping unreadable recovery entry {}: {}", p.display(), e);
+                        None
+                    }
+                }
+            })
+            .filter(|entry| !entry.id.starts_with(&self.session))
+            .collect();
+        entries.sort_by_key(|entry| entry.updated_ms);
+        entries
+    }
+
+    /// Run the debounced flusher on a background thread for the lifetime of the app
+    pub fn spawn_flusher(&self) {
+        let journal = self.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_POLL_INTERVAL);
+            journal.flush_due(FLUSH_DEBOUNCE);
+        });
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::file_io::{self, SaveError};
+use crate::views::ViewState;
+
+// File name of the session inside the app data directory
+pub const SESSION_FILE: &str = "session.json";
+
+/// Outer position and inner size of a window in physical pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+}
+
+/// One tab of a saved window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TabSession {
+    /// `None` for an empty or untitled document
+    pub path: Option<String>,
+    /// Opened with "Open Read-Only" or `--readonly`
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub view: ViewState,
+}
+
+/// One window of a saved session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowSession {
+    /// In the order they were shown
+    #[serde(default)]
+    pub tabs: Vec<TabSession>,
+    /// Index of the tab that was in front
+    #[serde(default)]
+    pub active: usize,
+    pub geometry: Option<WindowGeometry>,
+    /// Name of the monitor the window was on; it is only put back there if that
+    /// monitor is still connected
+    pub monitor: Option<String>,
+}
+
+/// The windows open when the app last quit, in the order they were opened
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Session {
+    pub windows: Vec<WindowSession>,
+}
+
+impl Session {
+    /// Read the session from `path`. A missing or unreadable file is an empty session.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("Ignoring invalid session file {}: {}", path.display(), e);
+                Session::default()
+            }),
+            Err(_) => Session::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_string_pretty(self).expect("sessions are always serializable");
+        file_io::atomic_write(path, json.as_bytes())
+    }
+}
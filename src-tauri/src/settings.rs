@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::backups::BackupSettings;
+use crate::dropped::DropSettings;
+use crate::file_io::{self, SaveError};
+use crate::history::HistorySettings;
+use crate::large_files::LargeFileSettings;
+use crate::line_endings::LineEndingPolicy;
+use crate::tabs::OpenFilesIn;
+use crate::views::ViewStateSettings;
+use crate::watcher::WatchSettings;
+
+// File name of the settings store inside the app config directory
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// User preferences persisted as JSON in the app config directory.
+/// Missing fields fall back to their defaults, so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub line_ending_policy: LineEndingPolicy,
+    pub backups: BackupSettings,
+    pub history: HistorySettings,
+    pub large_files: LargeFileSettings,
+    pub watching: WatchSettings,
+    /// Reopen the windows and documents of the last session when launched without a file
+    pub restore_session: bool,
+    pub view_state: ViewStateSettings,
+    pub open_files_in: OpenFilesIn,
+    pub drops: DropSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            line_ending_policy: LineEndingPolicy::Preserve,
+            backups: BackupSettings::default(),
+            history: HistorySettings::default(),
+            large_files: LargeFileSettings::default(),
+            watching: WatchSettings::default(),
+            restore_session: true,
+            view_state: ViewStateSettings::default(),
+            open_files_in: OpenFilesIn::default(),
+            drops: DropSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Read settings from `path`, falling back to defaults if the file is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("Ignoring invalid settings file {}: {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_string_pretty(self).expect("settings are always serializable");
+        file_io::atomic_write(path, json.as_bytes())
+    }
+}
+
+// Current settings, loaded at startup and updated through `update_settings`.
+// Newtype wrapper so Tauri's state manager sees a distinct TypeId.
+pub struct AppSettings(pub Arc<Mutex<Settings>>);
+impl Default for AppSettings {
+    fn default() -> Self { AppSettings(Arc::new(Mutex::new(Settings::default()))) }
+}
+
+impl AppSettings {
+    pub fn get(&self) -> Settings {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) {
+        *self.0.lock().unwrap() = settings;
+    }
+}
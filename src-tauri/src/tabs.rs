@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Where files opened from outside the app go (the file manager, the command line,
+/// drag and drop) when they aren't open already and no window is empty
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum OpenFilesIn {
+    /// A window of their own
+    #[default]
+    Window,
+    /// A new tab in the focused window
+    Tab,
+}
+
+/// The tabs of one window in the order they are shown, and the one in front
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TabList {
+    pub tabs: Vec<String>,
+    pub active: Option<String>,
+}
+
+impl TabList {
+    /// Take a document's tab out of the list. If it was in front, the tab to its
+    /// right takes its place, or the one to its left if it was the last.
+    fn remove(&mut self, document: &str) -> bool {
+        let Some(index) = self.tabs.iter().position(|tab| tab == document) else { return false };
+        self.tabs.remove(index);
+        if self.active.as_deref() == Some(document) {
+            self.active = self.tabs.get(index).or_else(|| self.tabs.last()).cloned();
+        }
+        true
+    }
+
+    /// Put a document's tab at `index`, or at the end past the last tab, and bring it to the front
+    fn insert(&mut self, index: usize, document: &str) {
+        self.tabs.insert(index.min(self.tabs.len()), document.to_string());
+        self.active = Some(document.to_string());
+    }
+}
+
+/// Which documents each window holds as tabs. A document id names one open document
+/// for as long as it is open, whichever window its tab is moved to, so the state
+/// kept per document is keyed by it rather than by window label.
+#[derive(Clone, Default)]
+pub struct Tabs(Arc<Mutex<HashMap<String, TabList>>>);
+
+impl Tabs {
+    /// Add a tab for `document` right after the window's tab in front, and bring it to the front
+    pub fn open(&self, window_label: &str, document: &str) {
+        let mut windows = self.0.lock().unwrap();
+        let list = windows.entry(window_label.to_string()).or_default();
+        let index = list.active.as_ref()
+            .and_then(|active| list.tabs.iter().position(|tab| tab == active))
+            .map_or(list.tabs.len(), |index| index + 1);
+        list.insert(index, document);
+    }
+
+    /// Remove a document's tab. Returns the window it was in.
+    pub fn close(&self, document: &str) -> Option<String> {
+        let mut windows = self.0.lock().unwrap();
+        windows.iter_mut()
+            .find_map(|(label, list)| list.remove(document).then(|| label.clone()))
+    }
+
+    /// Bring a document's tab to the front of its window. Returns the window.
+    pub fn activate(&self, document: &str) -> Option<String> {
+        let mut windows = self.0.lock().unwrap();
+        let (label, list) = windows.iter_mut().find(|(_, list)| list.tabs.iter().any(|tab| tab == document))?;
+        list.active = Some(document.to_string());
+        Some(label.clone())
+    }
+
+    /// Put a window's tabs in a new order. Refused unless `order` holds exactly
+    /// the documents the window has.
+    pub fn reorder(&self, window_label: &str, order: &[String]) -> bool {
+        let mut windows = self.0.lock().unwrap();
+        let Some(list) = windows.get_mut(window_label) else { return false };
+        let mut current = list.tabs.clone();
+        let mut requested = order.to_vec();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return false;
+        }
+        list.tabs = order.to_vec();
+        true
+    }
+
+    /// Move a document's tab to `window_label` at `index`, or to the end, and bring
+    /// it to the front there. Returns the window it came from.
+    pub fn move_to(&self, document: &str, window_label: &str, index: Option<usize>) -> Option<String> {
+        let mut windows = self.0.lock().unwrap();
+        let from = windows.iter_mut()
+            .find_map(|(label, list)| list.remove(document).then(|| label.clone()))?;
+        let list = windows.entry(window_label.to_string()).or_default();
+        list.insert(index.unwrap_or(usize::MAX), document);
+        Some(from)
+    }
+
+    pub fn window_of(&self, document: &str) -> Option<String> {
+        let windows = self.0.lock().unwrap();
+        windows.iter()
+            .find(|(_, list)| list.tabs.iter().any(|tab| tab == document))
+            .map(|(label, _)| label.clone())
+    }
+
+    pub fn list(&self, window_label: &str) -> TabList {
+        self.0.lock().unwrap().get(window_label).cloned().unwrap_or_default()
+    }
+
+    pub fn active(&self, window_label: &str) -> Option<String> {
+        self.0.lock().unwrap().get(window_label).and_then(|list| list.active.clone())
+    }
+
+    /// Forget a window, returning the documents its tabs held
+    pub fn remove_window(&self, window_label: &str) -> Vec<String> {
+        self.0.lock().unwrap().remove(window_label).map(|list| list.tabs).unwrap_or_default()
+    }
+}
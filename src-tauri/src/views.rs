@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::documents::canonical_key;
+use crate::file_io;
+
+// File name of the per-document view state store inside the app data directory
+pub const VIEW_STATE_FILE: &str = "view-state.json";
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// How the frontend was showing a document: reported while it is open, and handed
+/// back when its window is restored or the document is reopened
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ViewState {
+    pub reading_mode: bool,
+    /// Page zoom, 1.0 being 100%
+    pub zoom: f64,
+    /// Share of the window width taken by the editor pane
+    pub split_ratio: f64,
+    /// Editor scroll offset in pixels
+    pub scroll_top: f64,
+    /// Cursor position, 1-based like editor line numbers
+    pub cursor_line: u32,
+    pub cursor_column: u32,
+}
+
+impl Default for ViewState {
+    fn default() -> Self {
+        ViewState {
+            reading_mode: false,
+            zoom: 1.0,
+            split_ratio: 0.5,
+            scroll_top: 0.0,
+            cursor_line: 1,
+            cursor_column: 1,
+        }
+    }
+}
+
+/// Per-document view state preferences, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ViewStateSettings {
+    /// Documents not closed for this long are forgotten; 0 remembers them forever
+    pub max_age_days: u32,
+}
+
+impl Default for ViewStateSettings {
+    fn default() -> Self {
+        ViewStateSettings { max_age_days: 90 }
+    }
+}
+
+// View state per document id, kept current by the frontend and seeded from the
+// session when a window is restored. Newtype wrapper so Tauri's state manager
+// sees a distinct TypeId.
+pub struct OpenViews(pub Arc<Mutex<HashMap<String, ViewState>>>);
+impl Default for OpenViews {
+    fn default() -> Self { OpenViews(Arc::new(Mutex::new(HashMap::new()))) }
+}
+
+impl OpenViews {
+    pub fn set(&self, document: &str, view: ViewState) {
+        self.0.lock().unwrap().insert(document.to_string(), view);
+    }
+
+    pub fn get(&self, document: &str) -> Option<ViewState> {
+        self.0.lock().unwrap().get(document).copied()
+    }
+
+    pub fn remove(&self, document: &str) {
+        self.0.lock().unwrap().remove(document);
+    }
+}
+
+/// View state of one document, with when it was last closed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RememberedView {
+    view: ViewState,
+    closed_ms: u64,
+}
+
+#[derive(Default)]
+struct Store {
+    file: Option<PathBuf>,
+    views: HashMap<String, RememberedView>,
+}
+
+/// View state of recently closed documents, keyed by canonical path so every
+/// way of reaching a file shares one entry. Kept in memory and written through
+/// to a JSON file whenever a document is remembered.
+#[derive(Clone, Default)]
+pub struct DocumentViews(Arc<Mutex<Store>>);
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Drop entries for documents that haven't been closed within `max_age_days`
+fn prune(views: &mut HashMap<String, RememberedView>, settings: &ViewStateSettings) {
+    if settings.max_age_days == 0 {
+        return;
+    }
+    let cutoff = now_ms().saturating_sub(settings.max_age_days as u64 * MS_PER_DAY);
+    views.retain(|_, remembered| remembered.closed_ms >= cutoff);
+}
+
+impl DocumentViews {
+    /// Read the store from `file`, pruning stale entries. A missing or unreadable
+    /// file starts an empty store.
+    pub fn load(&self, file: PathBuf, settings: &ViewStateSettings) {
+        let mut views: HashMap<String, RememberedView> = fs::read_to_string(&file).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        prune(&mut views, settings);
+        *self.0.lock().unwrap() = Store { file: Some(file), views };
+    }
+
+    pub fn get(&self, path: &Path) -> Option<ViewState> {
+        self.0.lock().unwrap().views.get(&canonical_key(path)).map(|remembered| remembered.view)
+    }
+
+    /// Remember how `path` was shown as its tab closes
+    pub fn remember(&self, path: &Path, view: ViewState, settings: &ViewStateSettings) {
+        let mut store = self.0.lock().unwrap();
+        store.views.insert(canonical_key(path), RememberedView { view, closed_ms: now_ms() });
+        prune(&mut store.views, settings);
+        let Some(file) = &store.file else { return };
+        if let Some(parent) = file.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let json = serde_json::to_string(&store.views).expect("view states are always serializable");
+        if let Err(e) = file_io::atomic_write(file, json.as_bytes()) {
+            eprintln!("Failed to save view state store {}: {}", file.display(), e);
+        }
+    }
+}
@@ -0,0 +1,834 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::mounts;
+
+// Events for a watched file are coalesced until it has been quiet for this long.
+// Editors and sync tools often write a file in several steps.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+// How long the service thread waits for events when no burst is pending
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+
+// Shorter poll intervals are raised to this, since every poll rescans the directory
+const MIN_POLL_INTERVAL_MS: u64 = 100;
+
+/// How files are watched, globally or for the paths a rule matches
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum WatchMode {
+    /// Native notifications, or polling on network and FUSE filesystems where
+    /// those never arrive
+    #[default]
+    Auto,
+    Native,
+    Poll,
+}
+
+/// Watch mode for files whose path matches `pattern`, where `*` stands for any
+/// run of characters including path separators, e.g. `/mnt/nas/*`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchRule {
+    pub pattern: String,
+    pub mode: WatchMode,
+}
+
+/// File watching preferences, part of the persisted settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WatchSettings {
+    pub mode: WatchMode,
+    pub poll_interval_ms: u64,
+    /// Checked in order before `mode`; the first match wins
+    pub rules: Vec<WatchRule>,
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        WatchSettings {
+            mode: WatchMode::Auto,
+            poll_interval_ms: 2000,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// How a file is actually being watched
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WatchStrategy {
+    Native,
+    Poll,
+}
+
+/// What decided a file's watch strategy
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WatchReason {
+    /// A path rule asked for it
+    Rule,
+    /// The global watch mode asked for it
+    Setting,
+    /// Auto mode found a filesystem where native notifications are unreliable
+    Filesystem,
+    /// Auto mode on an ordinary local filesystem
+    Default,
+    /// Native watching broke and couldn't be set up again
+    Fallback,
+}
+
+/// The strategy chosen for a file, returned by `start_file_watcher`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchInfo {
+    pub strategy: WatchStrategy,
+    pub reason: WatchReason,
+    /// Type of the filesystem the file lives on, if known
+    pub filesystem: Option<String>,
+    pub poll_interval_ms: Option<u64>,
+}
+
+/// Match `text` against a pattern in which `*` stands for any run of characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is currently standing in for
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Decide how to watch the file at canonical path `path`
+pub fn choose_strategy(path: &Path, settings: &WatchSettings) -> WatchInfo {
+    let path_str = path.to_string_lossy();
+    let (mode, reason) = match settings.rules.iter().find(|rule| wildcard_match(&rule.pattern, &path_str)) {
+        Some(rule) => (rule.mode, WatchReason::Rule),
+        None => (settings.mode, WatchReason::Setting),
+    };
+    let filesystem = mounts::filesystem_type(path);
+    let (strategy, reason) = match mode {
+        WatchMode::Native => (WatchStrategy::Native, reason),
+        WatchMode::Poll => (WatchStrategy::Poll, reason),
+        WatchMode::Auto if filesystem.as_deref().is_some_and(mounts::is_remote_filesystem) => {
+            (WatchStrategy::Poll, WatchReason::Filesystem)
+        }
+        WatchMode::Auto => (WatchStrategy::Native, WatchReason::Default),
+    };
+    let poll_interval_ms = (strategy == WatchStrategy::Poll).then_some(settings.poll_interval_ms.max(MIN_POLL_INTERVAL_MS));
+    WatchInfo { strategy, reason, filesystem, poll_interval_ms }
+}
+
+/// Events seen for one file since its last burst settled
+#[derive(Default)]
+struct Burst {
+    last_event: Option<Instant>,
+    /// Where the file was moved, if the burst contained a rename away from its path
+    renamed_to: Option<PathBuf>,
+    // Some platforms report the two sides of a rename as separate events
+    renamed_away: bool,
+    /// Cookie tying the two sides of that rename together, where the platform has one
+    rename_tracker: Option<usize>,
+}
+
+impl Burst {
+    /// Take note of an event in the file's directory. Events for other entries are
+    /// ignored, except for the destination of a rename away from `path`.
+    fn observe(&mut self, event: &Event, path: &Path, now: Instant) {
+        let ours = event.paths.iter().any(|p| p == path);
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.first().is_some_and(|p| p == path) => {
+                self.renamed_to = event.paths.get(1).cloned();
+                self.renamed_away = false;
+                self.last_event = Some(now);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::Any)) if ours => {
+                self.renamed_away = true;
+                self.rename_tracker = event.tracker();
+                self.last_event = Some(now);
+            }
+            _ if self.is_destination(event) => {
+                self.renamed_to = event.paths.first().cloned();
+                self.renamed_away = false;
+            }
+            // Reads, and the close that follows a write, never change the content
+            EventKind::Access(_) => {}
+            _ if ours => self.last_event = Some(now),
+            _ => {}
+        }
+    }
+
+    /// Whether `event` is the other side of a rename away from the file. It may be in
+    /// any watched directory, so the file can be followed into another one.
+    fn is_destination(&self, event: &Event) -> bool {
+        let is_rename_to = matches!(event.kind, EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any)));
+        let same_rename = match (self.rename_tracker, event.tracker()) {
+            (Some(ours), Some(theirs)) => ours == theirs,
+            _ => true,
+        };
+        self.renamed_away && is_rename_to && same_rename
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.last_event.map(|last| last + WATCH_DEBOUNCE)
+    }
+
+    /// End the burst if it has been quiet long enough, returning the rename destination
+    fn settle_due(&mut self, now: Instant) -> Option<Option<PathBuf>> {
+        if self.deadline()? > now {
+            return None;
+        }
+        self.last_event = None;
+        self.renamed_away = false;
+        self.rename_tracker = None;
+        Some(self.renamed_to.take())
+    }
+}
+
+/// What happened to a watched file, judged from the disk once a burst settled
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileChange {
+    /// Written in place or replaced by a rename over it
+    Modified,
+    Deleted,
+    /// Moved to a new path, which the watch now follows
+    Renamed { from: PathBuf },
+    /// Back at its path after having been deleted
+    Restored,
+}
+
+/// What tells a file apart from another one at the same path: the device and
+/// inode on Unix. `None` where the platform offers nothing cheaper than the content.
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).ok().map(|meta| (meta.dev(), meta.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Where the file behind an open handle is now, even after it was moved to a
+/// directory nobody watches. Linux only, from `/proc/self/fd`.
+#[cfg(target_os = "linux")]
+fn handle_path(handle: &fs::File) -> Option<PathBuf> {
+    use std::os::fd::AsRawFd;
+    let target = fs::read_link(format!("/proc/self/fd/{}", handle.as_raw_fd())).ok()?;
+    (!target.to_string_lossy().ends_with(" (deleted)")).then_some(target)
+}
+
+/// The file a watcher follows across renames, deletions and atomic replaces
+pub struct WatchedFile {
+    path: PathBuf,
+    missing: bool,
+    identity: Option<(u64, u64)>,
+    // Kept open to find the file again after a move the directory watch can't see
+    #[cfg(target_os = "linux")]
+    handle: Option<fs::File>,
+}
+
+impl WatchedFile {
+    pub fn new(path: PathBuf) -> Self {
+        let mut file = WatchedFile {
+            path,
+            missing: false,
+            identity: None,
+            #[cfg(target_os = "linux")]
+            handle: None,
+        };
+        file.remember_identity();
+        file
+    }
+
+    /// Note which file is at the path now, e.g. after an atomic replace put a new one there
+    fn remember_identity(&mut self) {
+        self.identity = file_identity(&self.path);
+        #[cfg(target_os = "linux")]
+        {
+            self.handle = fs::File::open(&self.path).ok();
+        }
+    }
+
+    /// Whether `path` is the file being followed, as far as the platform can tell
+    fn is_same_file(&self, path: &Path) -> bool {
+        path.is_file() && (self.identity.is_none() || file_identity(path) == self.identity)
+    }
+
+    /// Where the file went when the rename's destination wasn't reported
+    fn moved_to(&self) -> Option<PathBuf> {
+        #[cfg(target_os = "linux")]
+        {
+            self.handle.as_ref().and_then(handle_path).filter(|path| self.is_same_file(path))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Work out what a settled burst did to the file. Returns `None` when there is
+    /// nothing new to report, e.g. further events after the file was deleted.
+    pub fn settle(&mut self, renamed_to: Option<PathBuf>) -> Option<FileChange> {
+        // A temp file renamed over the path counts as a modification
+        if self.path.is_file() {
+            self.remember_identity();
+            if self.missing {
+                self.missing = false;
+                return Some(FileChange::Restored);
+            }
+            return Some(FileChange::Modified);
+        }
+        // Check the reported destination is the same file before following it, and
+        // look for the file itself if it left for a directory that isn't watched
+        let moved_to = renamed_to.filter(|to| self.is_same_file(to)).or_else(|| self.moved_to());
+        if let Some(to) = moved_to {
+            let from = std::mem::replace(&mut self.path, to);
+            self.missing = false;
+            return Some(FileChange::Renamed { from });
+        }
+        if self.missing {
+            return None;
+        }
+        self.missing = true;
+        Some(FileChange::Deleted)
+    }
+}
+
+/// Decides whether the content found on disk is news to a window. Content the
+/// app loaded or wrote itself is not, and neither is content already reported.
+#[derive(Default)]
+pub struct ChangeFilter {
+    reported: Option<String>,
+}
+
+impl ChangeFilter {
+    /// `known_hash` is the hash of what the window last loaded or saved
+    pub fn is_external(&mut self, disk_hash: &str, known_hash: Option<&str>) -> bool {
+        if known_hash == Some(disk_hash) {
+            // Back in step with the window, so the next difference is news again
+            self.reported = None;
+            return false;
+        }
+        if self.reported.as_deref() == Some(disk_hash) {
+            return false;
+        }
+        self.reported = Some(disk_hash.to_string());
+        true
+    }
+}
+
+/// Why a window watches a file
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionKind {
+    /// The document open in the window
+    Document,
+    /// An image or file the document links to, refreshed in the preview
+    Asset,
+}
+
+/// A window watching a file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscriber {
+    pub window_label: String,
+    /// The path as the window knows it, which may differ from the canonical one
+    pub path: String,
+    pub kind: SubscriptionKind,
+}
+
+impl Subscriber {
+    fn is(&self, window_label: &str, kind: SubscriptionKind) -> bool {
+        self.window_label == window_label && self.kind == kind
+    }
+}
+
+/// A watched file and the windows subscribed to it, as listed by `debug_watchers`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchSubscription {
+    pub path: String,
+    pub directory: String,
+    pub watch: WatchInfo,
+    pub subscribers: Vec<Subscriber>,
+}
+
+/// Something for the subscribers of a file to hear about
+pub enum WatchNotice {
+    /// `path` is where the file is now, after following any rename. The
+    /// subscribers carry the paths they knew the file by before the change.
+    Changed { path: PathBuf, change: FileChange, subscribers: Vec<Subscriber> },
+    /// The OS watch failed; the affected subscriptions have been dropped
+    Failed { error: notify::Error, subscribers: Vec<Subscriber> },
+    /// The OS watch failed without naming a path, so every watch was set up again.
+    /// `subscribers` are still watched, `dropped` could not be watched any more.
+    Rearmed { error: notify::Error, subscribers: Vec<Subscriber>, dropped: Vec<Subscriber> },
+}
+
+struct WatchEntry {
+    file: WatchedFile,
+    dir: PathBuf,
+    info: WatchInfo,
+    subscribers: Vec<Subscriber>,
+    filters: HashMap<String, ChangeFilter>,
+    burst: Burst,
+}
+
+struct ServiceState {
+    events: Sender<notify::Result<Event>>,
+    /// Each watcher is created with the first directory it watches and dropped with the last
+    native: Option<RecommendedWatcher>,
+    poll: Option<PollWatcher>,
+    poll_interval: Duration,
+    /// Number of watched files per watched directory and strategy
+    dirs: HashMap<(PathBuf, WatchStrategy), usize>,
+    /// Keyed by canonical path
+    files: HashMap<PathBuf, WatchEntry>,
+}
+
+impl ServiceState {
+    fn watch_dir(&mut self, dir: &Path, strategy: WatchStrategy) -> notify::Result<()> {
+        match strategy {
+            WatchStrategy::Native => {
+                if self.native.is_none() {
+                    self.native = Some(RecommendedWatcher::new(self.events.clone(), Config::default())?);
+                }
+                if let Some(watcher) = &mut self.native {
+                    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                }
+            }
+            WatchStrategy::Poll => {
+                if self.poll.is_none() {
+                    let config = Config::default().with_poll_interval(self.poll_interval);
+                    self.poll = Some(PollWatcher::new(self.events.clone(), config)?);
+                }
+                if let Some(watcher) = &mut self.poll {
+                    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn retain_dir(&mut self, dir: &Path, strategy: WatchStrategy) -> notify::Result<()> {
+        let key = (dir.to_path_buf(), strategy);
+        if !self.dirs.contains_key(&key) {
+            self.watch_dir(dir, strategy)?;
+        }
+        *self.dirs.entry(key).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn release_dir(&mut self, dir: &Path, strategy: WatchStrategy) {
+        let key = (dir.to_path_buf(), strategy);
+        let Some(count) = self.dirs.get_mut(&key) else { return };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        self.dirs.remove(&key);
+        let still_used = self.dirs.keys().any(|(_, s)| *s == strategy);
+        match strategy {
+            WatchStrategy::Native if still_used => {
+                if let Some(watcher) = &mut self.native {
+                    let _ = watcher.unwatch(dir);
+                }
+            }
+            WatchStrategy::Native => self.native = None,
+            WatchStrategy::Poll if still_used => {
+                if let Some(watcher) = &mut self.poll {
+                    let _ = watcher.unwatch(dir);
+                }
+            }
+            WatchStrategy::Poll => self.poll = None,
+        }
+    }
+
+    /// Poll at a new interval, recreating the poll watcher if it is running. The
+    /// new watcher only replaces the old one once it watches every polled directory,
+    /// so on error the old interval and watches stay in place.
+    fn set_poll_interval(&mut self, interval_ms: u64) -> notify::Result<()> {
+        let interval = Duration::from_millis(interval_ms);
+        if interval == self.poll_interval {
+            return Ok(());
+        }
+        if self.poll.is_some() {
+            let config = Config::default().with_poll_interval(interval);
+            let mut watcher = PollWatcher::new(self.events.clone(), config)?;
+            for (dir, _) in self.dirs.keys().filter(|(_, strategy)| *strategy == WatchStrategy::Poll) {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            }
+            self.poll = Some(watcher);
+        }
+        self.poll_interval = interval;
+        for entry in self.files.values_mut().filter(|e| e.info.strategy == WatchStrategy::Poll) {
+            entry.info.poll_interval_ms = Some(interval_ms);
+        }
+        Ok(())
+    }
+
+    /// Set every watch up again from scratch. Directories that can no longer be
+    /// watched natively are polled instead. Returns the keys of the files that
+    /// can't be watched at all.
+    fn rearm(&mut self) -> Vec<PathBuf> {
+        self.native = None;
+        self.poll = None;
+        let mut failed = Vec::new();
+        let dirs: Vec<(PathBuf, WatchStrategy)> = self.dirs.keys().cloned().collect();
+        for (dir, strategy) in dirs {
+            if self.watch_dir(&dir, strategy).is_ok() {
+                continue;
+            }
+            if strategy == WatchStrategy::Native && self.watch_dir(&dir, WatchStrategy::Poll).is_ok() {
+                eprintln!("Native watch of {} could not be restored; polling it instead", dir.display());
+                let count = self.dirs.remove(&(dir.clone(), strategy)).unwrap_or(0);
+                *self.dirs.entry((dir.clone(), WatchStrategy::Poll)).or_insert(0) += count;
+                let interval_ms = self.poll_interval.as_millis() as u64;
+                for entry in self.files.values_mut().filter(|e| e.dir == dir && e.info.strategy == strategy) {
+                    entry.info.strategy = WatchStrategy::Poll;
+                    entry.info.reason = WatchReason::Fallback;
+                    entry.info.poll_interval_ms = Some(interval_ms);
+                }
+                continue;
+            }
+            failed.extend(self.files.iter()
+                .filter(|(_, entry)| entry.dir == dir && entry.info.strategy == strategy)
+                .map(|(key, _)| key.clone()));
+        }
+        failed
+    }
+
+    /// Drop the subscribers of a file matching `remove`, and the file itself once
+    /// nobody is left. Returns the dropped subscribers.
+    fn drop_subscribers(&mut self, key: &Path, remove: impl Fn(&Subscriber) -> bool) -> Vec<Subscriber> {
+        let Some(entry) = self.files.get_mut(key) else { return Vec::new() };
+        let (dropped, kept): (Vec<Subscriber>, Vec<Subscriber>) = entry.subscribers.drain(..).partition(|s| remove(s));
+        entry.subscribers = kept;
+        for subscriber in &dropped {
+            if !entry.subscribers.iter().any(|s| s.window_label == subscriber.window_label) {
+                entry.filters.remove(&subscriber.window_label);
+            }
+        }
+        if entry.subscribers.is_empty() {
+            let (dir, strategy) = (entry.dir.clone(), entry.info.strategy);
+            self.files.remove(key);
+            self.release_dir(&dir, strategy);
+        }
+        dropped
+    }
+
+    /// Move a renamed file's entry to its new path
+    fn rekey(&mut self, old_key: &Path) {
+        let Some(mut entry) = self.files.remove(old_key) else { return };
+        let new_key = entry.file.path().to_path_buf();
+        let strategy = entry.info.strategy;
+        if let Some(new_dir) = new_key.parent().filter(|dir| *dir != entry.dir) {
+            match self.retain_dir(new_dir, strategy) {
+                Ok(()) => {
+                    let old_dir = std::mem::replace(&mut entry.dir, new_dir.to_path_buf());
+                    self.release_dir(&old_dir, strategy);
+                }
+                Err(e) => eprintln!("Failed to follow {} to its new directory: {}", new_key.display(), e),
+            }
+        }
+        match self.files.get_mut(&new_key) {
+            // Renamed onto a file that is watched already
+            Some(existing) => {
+                existing.subscribers.extend(entry.subscribers);
+                existing.filters.extend(entry.filters);
+                self.release_dir(&entry.dir, strategy);
+            }
+            None => {
+                self.files.insert(new_key, entry);
+            }
+        }
+    }
+}
+
+/// One OS watcher and one thread shared by every window. Subscriptions are
+/// reference-counted by canonical path, so two windows showing the same file
+/// share a watch, and each change is fanned out to all of them. The parent
+/// directory is watched rather than the file itself, so a watch survives editors
+/// that save by renaming a temp file over the original. Files on filesystems
+/// without reliable native notifications are polled instead.
+#[derive(Clone)]
+pub struct WatcherService {
+    state: Arc<Mutex<ServiceState>>,
+    receiver: Arc<Mutex<Option<Receiver<notify::Result<Event>>>>>,
+}
+
+impl Default for WatcherService {
+    fn default() -> Self {
+        let (events, receiver) = mpsc::channel();
+        WatcherService {
+            state: Arc::new(Mutex::new(ServiceState {
+                events,
+                native: None,
+                poll: None,
+                poll_interval: Duration::from_millis(WatchSettings::default().poll_interval_ms),
+                dirs: HashMap::new(),
+                files: HashMap::new(),
+            })),
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+        }
+    }
+}
+
+impl WatcherService {
+    /// Watch the document at `path` on behalf of a window, returning how it is watched.
+    /// A file that is watched already keeps its strategy. Subscribing twice is harmless.
+    pub fn subscribe(&self, window_label: &str, path: &str, settings: &WatchSettings) -> notify::Result<WatchInfo> {
+        let mut state = self.state.lock().unwrap();
+        Self::add_subscriber(&mut state, window_label, path, SubscriptionKind::Document, settings)
+    }
+
+    fn add_subscriber(
+        state: &mut ServiceState,
+        window_label: &str,
+        path: &str,
+        kind: SubscriptionKind,
+        settings: &WatchSettings,
+    ) -> notify::Result<WatchInfo> {
+        let canonical = fs::canonicalize(path)?;
+        let dir = canonical.parent()
+            .ok_or_else(|| notify::Error::generic("File has no parent directory"))?
+            .to_path_buf();
+        let subscriber = Subscriber { window_label: window_label.to_string(), path: path.to_string(), kind };
+
+        if let Some(entry) = state.files.get_mut(&canonical) {
+            if !entry.subscribers.iter().any(|s| s.is(window_label, kind) && s.path == path) {
+                entry.subscribers.push(subscriber);
+            }
+            return Ok(entry.info.clone());
+        }
+        let info = choose_strategy(&canonical, settings);
+        if info.strategy == WatchStrategy::Poll {
+            state.set_poll_interval(info.poll_interval_ms.unwrap_or(MIN_POLL_INTERVAL_MS))?;
+        }
+        state.retain_dir(&dir, info.strategy)?;
+        state.files.insert(canonical.clone(), WatchEntry {
+            file: WatchedFile::new(canonical),
+            dir,
+            info: info.clone(),
+            subscribers: vec![subscriber],
+            filters: HashMap::new(),
+            burst: Burst::default(),
+        });
+        Ok(info)
+    }
+
+    /// Stop watching the document at `path` for a window. Returns false if it wasn't subscribed.
+    pub fn unsubscribe(&self, window_label: &str, path: &str) -> bool {
+        let document = SubscriptionKind::Document;
+        // The file may be gone, so subscriptions are also matched by the window's own path
+        let canonical = fs::canonicalize(path).ok();
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<PathBuf> = state.files.iter()
+            .filter(|(key, entry)| {
+                canonical.as_deref() == Some(key.as_path())
+                    || entry.subscribers.iter().any(|s| s.is(window_label, document) && s.path == path)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut removed = false;
+        for key in keys {
+            removed |= !state.drop_subscribers(&key, |s| s.is(window_label, document)).is_empty();
+        }
+        removed
+    }
+
+    /// Make `assets` the files a window watches for its preview, dropping assets it
+    /// no longer links to. Files that can't be watched are skipped. Returns the
+    /// watched paths.
+    pub fn set_assets(&self, window_label: &str, assets: &[PathBuf], settings: &WatchSettings) -> Vec<String> {
+        let asset = SubscriptionKind::Asset;
+        let wanted: Vec<String> = assets.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        let mut state = self.state.lock().unwrap();
+        let stale: Vec<PathBuf> = state.files.iter()
+            .filter(|(_, entry)| entry.subscribers.iter().any(|s| s.is(window_label, asset) && !wanted.contains(&s.path)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            state.drop_subscribers(&key, |s| s.is(window_label, asset) && !wanted.contains(&s.path));
+        }
+        wanted.into_iter()
+            .filter(|path| match Self::add_subscriber(&mut state, window_label, path, asset, settings) {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("Not watching asset {}: {}", path, e);
+                    false
+                }
+            })
+            .collect()
+    }
+
+    /// Drop every subscription of a window. Returns the paths it was watching.
+    pub fn unsubscribe_window(&self, window_label: &str) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<PathBuf> = state.files.keys().cloned().collect();
+        keys.iter()
+            .flat_map(|key| state.drop_subscribers(key, |s| s.window_label == window_label))
+            .map(|s| s.path)
+            .collect()
+    }
+
+    /// Decide whether `disk_hash` is news to a window, see `ChangeFilter`.
+    /// `path` is the canonical path from a `WatchNotice`.
+    pub fn is_external(&self, path: &Path, window_label: &str, disk_hash: &str, known_hash: Option<&str>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(entry) = state.files.get_mut(path) else { return false };
+        entry.filters.entry(window_label.to_string()).or_default().is_external(disk_hash, known_hash)
+    }
+
+    /// Active subscriptions, for debugging
+    pub fn subscriptions(&self) -> Vec<WatchSubscription> {
+        let state = self.state.lock().unwrap();
+        let mut subscriptions: Vec<WatchSubscription> = state.files.values()
+            .map(|entry| WatchSubscription {
+                path: entry.file.path().to_string_lossy().to_string(),
+                directory: entry.dir.to_string_lossy().to_string(),
+                watch: entry.info.clone(),
+                subscribers: entry.subscribers.clone(),
+            })
+            .collect();
+        subscriptions.sort_by(|a, b| a.path.cmp(&b.path));
+        subscriptions
+    }
+
+    fn observe(&self, event: &Event) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for entry in state.files.values_mut() {
+            let in_dir = event.paths.iter().any(|p| p.parent() == Some(entry.dir.as_path()));
+            if in_dir || entry.burst.is_destination(event) {
+                let path = entry.file.path().to_path_buf();
+                entry.burst.observe(event, &path, now);
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let state = self.state.lock().unwrap();
+        state.files.values().filter_map(|entry| entry.burst.deadline()).min()
+    }
+
+    /// Judge every file whose burst has settled
+    fn settle_due(&self) -> Vec<WatchNotice> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let mut notices = Vec::new();
+        let mut renamed = Vec::new();
+        for (key, entry) in state.files.iter_mut() {
+            let Some(renamed_to) = entry.burst.settle_due(now) else { continue };
+            let Some(change) = entry.file.settle(renamed_to) else { continue };
+            let subscribers = entry.subscribers.clone();
+            let path = entry.file.path().to_path_buf();
+            if matches!(change, FileChange::Renamed { .. }) {
+                for subscriber in &mut entry.subscribers {
+                    subscriber.path = path.to_string_lossy().to_string();
+                }
+                renamed.push(key.clone());
+            }
+            notices.push(WatchNotice::Changed { path, change, subscribers });
+        }
+        for key in renamed {
+            state.rekey(&key);
+        }
+        notices
+    }
+
+    /// Drop the subscriptions a watch error concerns: those of the files or
+    /// directories it names. An error that names none may have broken any watch,
+    /// so every watch is set up again instead.
+    fn fail(&self, error: notify::Error) -> WatchNotice {
+        let mut state = self.state.lock().unwrap();
+        if error.paths.is_empty() {
+            let failed = state.rearm();
+            let dropped = failed.iter().flat_map(|key| state.drop_subscribers(key, |_| true)).collect();
+            let subscribers = state.files.values().flat_map(|entry| entry.subscribers.clone()).collect();
+            return WatchNotice::Rearmed { error, subscribers, dropped };
+        }
+        let keys: Vec<PathBuf> = state.files.iter()
+            .filter(|(key, entry)| error.paths.iter().any(|p| p == *key || *p == entry.dir))
+            .map(|(key, _)| key.clone())
+            .collect();
+        let subscribers = keys.iter().flat_map(|key| state.drop_subscribers(key, |_| true)).collect();
+        WatchNotice::Failed { error, subscribers }
+    }
+
+    /// Run the service on a background thread for the lifetime of the app, passing
+    /// notices to `handler` on that thread, batched when several files settle together
+    pub fn spawn(&self, handler: impl Fn(Vec<WatchNotice>) + Send + 'static) {
+        let receiver = self.receiver.lock().unwrap().take().expect("the watcher service is spawned once");
+        let service = self.clone();
+        std::thread::spawn(move || loop {
+            let wait = service.next_deadline()
+                .map_or(IDLE_WAIT, |deadline| deadline.saturating_duration_since(Instant::now()));
+            match receiver.recv_timeout(wait) {
+                Ok(Ok(event)) => service.observe(&event),
+                Ok(Err(error)) => handler(vec![service.fail(error)]),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            let notices = service.settle_due();
+            if !notices.is_empty() {
+                handler(notices);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_matches_whole_text() {
+        assert!(wildcard_match("/mnt/*", "/mnt/share/notes.md"));
+        assert!(wildcard_match("*.md", "notes.md"));
+        assert!(!wildcard_match("*.md", "notes.md.bak"));
+        assert!(wildcard_match("/Volumes/*/docs/*", "/Volumes/Server/docs/a.md"));
+        assert!(!wildcard_match("/Volumes/*/docs/*", "/Volumes/Server/notes/a.md"));
+        assert!(wildcard_match("exact", "exact"));
+        assert!(!wildcard_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn wildcard_backtracks_past_false_starts() {
+        assert!(wildcard_match("*ab*ab", "xabyabab"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("a*b*c", "aXbYbZ"));
+        assert!(wildcard_match("**", ""));
+        assert!(!wildcard_match("?", "x"));
+    }
+}
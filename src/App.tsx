@@ -2,14 +2,220 @@ import { useEffect, useState, useRef, useCallback } from 'react'
 import * as monaco from 'monaco-editor'
 import './App.css'
 import './styles/highlight.css'
-import { SplitView, SourceEditor, PreviewPane } from './components'
+import { SplitView, SourceEditor, PreviewPane, disposeEditorModel } from './components'
 import { scrollSyncService } from './services/scrollSync'
 
+// Document metadata sent by the backend alongside file content
+interface DocumentInfo {
+  encoding: { name: string; bom: boolean }
+  lineEndings: { dominant: 'lf' | 'crlf'; mixed: boolean; lfCount: number; crlfCount: number }
+  // Unsaved content restored from the crash-recovery journal
+  recovered: boolean
+  // Content has changes not saved to the file, as when recovered or moved from another window
+  unsaved: boolean
+  // Why the document can't be saved back to its file, if it can't
+  readOnly: ReadOnlyReason | null
+  // How the document was shown when it was last closed
+  view: ViewState | null
+}
+
+type ReadOnlyReason = 'notWritable' | 'requested' | 'partial'
+
+// Sent in place of a document for files too large to load in one go
+interface LargeFileNotice {
+  path: string
+  size: number
+  warn: boolean
+  previewBytes: number
+  readOnly: boolean
+}
+
+// How the backend watches the current file, returned by `start_file_watcher`
+interface WatchInfo {
+  strategy: 'native' | 'poll'
+  reason: 'rule' | 'setting' | 'filesystem' | 'default' | 'fallback'
+  filesystem: string | null
+  pollIntervalMs: number | null
+}
+
+// Whole lines replaced in the editor, 1-based; `text` ends in a line break unless
+// it runs to the end of the file
+interface LineEdit {
+  startLine: number
+  deletedLines: number
+  text: string
+}
+
+// New content sent with `file-changed-externally`: edits against the version the
+// window last acknowledged, or the whole text
+type ExternalContent =
+  | { kind: 'full'; content: string }
+  | { kind: 'delta'; edits: LineEdit[] }
+
+// Apply line edits to text outside the editor, with the same clamping Monaco applies
+// to lines past the end
+function applyLineEdits(text: string, edits: LineEdit[]): string {
+  // Apply from the bottom up so earlier line numbers stay valid
+  return [...edits].sort((a, b) => b.startLine - a.startLine).reduce((result, edit) => {
+    const lineStart = (line: number) => {
+      let offset = 0
+      for (let i = 1; i < line; i++) {
+        const next = result.indexOf('\n', offset)
+        if (next === -1) return result.length
+        offset = next + 1
+      }
+      return offset
+    }
+    const start = lineStart(edit.startLine)
+    const end = lineStart(edit.startLine + edit.deletedLines)
+    return result.slice(0, start) + edit.text + result.slice(end)
+  }, text)
+}
+
+// How a window shows its document, kept by the backend for session restore
+// and for reopening the document later
+interface ViewState {
+  readingMode: boolean
+  zoom: number
+  splitRatio: number
+  scrollTop: number
+  cursorLine: number
+  cursorColumn: number
+}
+
+// Lines of one conflict left by `merge_external_change`, 1-based
+interface MergeConflict {
+  startLine: number
+  separatorLine: number
+  endLine: number
+}
+
+// An external change merged into the unsaved buffer
+interface MergeOutcome {
+  content: string
+  conflicts: MergeConflict[]
+}
+
+// One decoded piece of a file being streamed by `open_file_chunked`
+interface OpenProgress {
+  path: string
+  loaded: number
+  total: number
+  chunk: string
+}
+
+// A backup kept by backup-on-save, as returned by `list_backups`
+interface BackupInfo {
+  path: string
+  location: 'tilde' | 'hidden' | 'central'
+  slot: number
+  modifiedMs: number | null
+  size: number
+}
+
+// A local history snapshot, as returned by `list_history`
+interface Snapshot {
+  id: string
+  hash: string
+  timestampMs: number
+  size: number
+  source: 'save' | 'external'
+  label: string | null
+}
+
+// Error shape shared by all backend commands and the `file-error` event
+interface AppError {
+  kind: string
+  message: string
+  path: string | null
+  osError: number | null
+}
+
+// What a tab shows when it reaches the window: a document as `[path, content, info]`,
+// with no path for untitled documents, or a large file to stream
+type TabOpen = [string | null, string, DocumentInfo] | LargeFileNotice
+
+// A tab sent with `tab-opened` or returned by `window_ready`; `open` is null for an empty tab
+interface PendingTab {
+  id: string
+  open: TabOpen | null
+}
+
+// The tabs a window starts with, returned by `window_ready`
+interface ReadyTabs {
+  tabs: PendingTab[]
+  active: string | null
+}
+
+// Everything a tab keeps while it is in the background
+interface TabState {
+  content: string
+  currentFile: string | null
+  hasUnsavedChanges: boolean
+  readOnly: ReadOnlyReason | null
+  isOrphaned: boolean
+  watchInfo: WatchInfo | null
+  mergeConflicts: MergeConflict[]
+  view: ViewState | null
+  // Changed on disk while it had unsaved changes; a merge is offered when it comes back
+  changedOnDisk: boolean
+  // A large file that streams in once the tab comes to the front
+  largeFile: LargeFileNotice | null
+}
+
+// One of the window's documents, named by the id the backend gave it
+interface Tab {
+  id: string
+  state: TabState
+}
+
+const emptyTabState = (): TabState => ({
+  content: '',
+  currentFile: null,
+  hasUnsavedChanges: false,
+  readOnly: null,
+  isOrphaned: false,
+  watchInfo: null,
+  mergeConflicts: [],
+  view: null,
+  changedOnDisk: false,
+  largeFile: null,
+})
+
+// The state of a tab from what the backend sent for it
+const tabStateFor = (open: TabOpen | null): TabState => {
+  const state = emptyTabState()
+  if (!open) return state
+  if (!Array.isArray(open)) return { ...state, largeFile: open }
+  const [filePath, fileContent, info] = open
+  return {
+    ...state,
+    content: fileContent,
+    currentFile: filePath,
+    hasUnsavedChanges: info.unsaved || info.recovered,
+    readOnly: info.readOnly,
+    view: info.view,
+  }
+}
+
+// File name shown for a tab
+const tabName = (filePath: string | null): string =>
+  filePath?.split(/[\\/]/).pop() ?? 'Untitled'
+
 function App() {
 
   const [content, setContent] = useState('')
   const [currentFile, setCurrentFile] = useState<string | null>(null)
   const [hasUnsavedChanges, setHasUnsavedChanges] = useState(false)
+  const [readOnly, setReadOnly] = useState<ReadOnlyReason | null>(null)
+  // The file was deleted or moved away on disk; saving recreates it
+  const [isOrphaned, setIsOrphaned] = useState(false)
+  const [watchInfo, setWatchInfo] = useState<WatchInfo | null>(null)
+  // Bumped when a linked image or file changes on disk, to reload it in the preview
+  const [assetVersion, setAssetVersion] = useState(0)
+  // Conflicts left in the buffer by the last merge of an external change
+  const [mergeConflicts, setMergeConflicts] = useState<MergeConflict[]>([])
+  const [openProgress, setOpenProgress] = useState<{ path: string; loaded: number; total: number } | null>(null)
   const [isDarkTheme, setIsDarkTheme] = useState(() => {
     const saved = localStorage.getItem('markdown-editor-theme')
     return saved === 'dark'
@@ -20,6 +226,10 @@ function App() {
   const [isWeb, setIsWeb] = useState(false) // Start as false (hide buttons), set true only if web
   const [debugInfo, setDebugInfo] = useState<string>('')
   const [isReadingMode, setIsReadingMode] = useState(false)
+  // The window's tabs in order. The one in front is shown from the editor state
+  // above; its stored state is only brought up to date when it goes to the background.
+  const [tabs, setTabs] = useState<Tab[]>([])
+  const [activeTab, setActiveTab] = useState<string | null>(null)
   const isTauri = !isWeb // Derived value for backward compatibility
   
   // Use a ref to track the current theme state to avoid stale closures
@@ -28,8 +238,41 @@ function App() {
   const hasUnsavedChangesRef = useRef(hasUnsavedChanges)
   // Use a ref to track the current file to avoid stale closures
   const currentFileRef = useRef(currentFile)
+  // Use a ref to track the editor content for handlers registered once at startup
+  const contentRef = useRef(content)
+  // Chunks of a large file received so far, joined once the last one arrives
+  const openChunksRef = useRef<string[]>([])
   // Use a ref to track the Monaco editor instance for clipboard operations
   const editorRef = useRef<monaco.editor.IStandaloneCodeEditor | null>(null)
+  // Use a ref to track reading mode for the view state reports
+  const isReadingModeRef = useRef(isReadingMode)
+  // Editor share of the window width; new documents get the last ratio used anywhere
+  const [splitRatio, setSplitRatio] = useState(() => parseFloat(localStorage.getItem('split-view-ratio') ?? '0.5'))
+  const splitRatioRef = useRef(splitRatio)
+  // Restored view state whose cursor and scroll position wait for the editor to have the content
+  const pendingViewRef = useRef<ViewState | null>(null)
+  // The view last reported, for the editor fields while the editor is hidden in reading mode
+  const lastViewRef = useRef<ViewState | null>(null)
+  // View state is only reported once the restored one has been fetched, so the
+  // defaults of a fresh window never overwrite it
+  const viewLoadedRef = useRef(false)
+  const viewReportTimerRef = useRef<number | null>(null)
+  // The latest save handler, for the save the backend asks for before closing the window
+  const saveFileRef = useRef<(() => Promise<boolean>) | null>(null)
+  // Tabs and the one in front for handlers registered once at startup. Both are set
+  // together with their state so a tab switch is seen right away.
+  const tabsRef = useRef(tabs)
+  const activeTabRef = useRef(activeTab)
+  // The tab whose model the editor holds. It follows the tab in front once the
+  // editor has switched models, which happens as the new tab renders.
+  const shownTabRef = useRef(activeTab)
+  // The tab in front as it stands, for putting it in the background
+  const frontTabRef = useRef<TabState>(emptyTabState())
+  // Closed tabs whose editor models are disposed once the editor no longer shows them
+  const closedTabsRef = useRef<string[]>([])
+  // The tab being dragged to a new place in the tab strip, and whether it has moved
+  const draggedTabRef = useRef<string | null>(null)
+  const tabsReorderedRef = useRef(false)
   
   // Update the refs whenever the state changes
   useEffect(() => {
@@ -44,6 +287,35 @@ function App() {
     currentFileRef.current = currentFile
   }, [currentFile])
 
+  useEffect(() => {
+    contentRef.current = content
+  }, [content])
+
+  useEffect(() => {
+    frontTabRef.current = {
+      content,
+      currentFile,
+      hasUnsavedChanges,
+      readOnly,
+      isOrphaned,
+      watchInfo,
+      mergeConflicts,
+      view: null,
+      changedOnDisk: false,
+      largeFile: null,
+    }
+  }, [content, currentFile, hasUnsavedChanges, readOnly, isOrphaned, watchInfo, mergeConflicts])
+
+  useEffect(() => {
+    isReadingModeRef.current = isReadingMode
+    reportViewState()
+  }, [isReadingMode])
+
+  useEffect(() => {
+    splitRatioRef.current = splitRatio
+    reportViewState()
+  }, [splitRatio])
+
   useEffect(() => {
     console.log('Markdown Editor initialized');
     console.info('Frontend loaded successfully');
@@ -91,33 +363,30 @@ function App() {
         const { getCurrentWindow } = await import('@tauri-apps/api/window');
         const currentWindow = getCurrentWindow();
 
-        // Set up the file-opened listener on the CURRENT WINDOW (not global)
-        const fileOpenedListener = await currentWindow.listen<[string, string]>('file-opened', (event) => {
-          console.log('File opened event received:', event);
-          console.log('Event payload:', event.payload);
-          console.log('Payload type:', typeof event.payload);
-          console.log('Is array:', Array.isArray(event.payload));
-          if (Array.isArray(event.payload) && event.payload.length === 2) {
-            const [filePath, fileContent] = event.payload;
-            console.log('Setting file:', filePath, 'with content length:', fileContent.length);
-            setCurrentFile(filePath);
-            setContent(fileContent);
-            setHasUnsavedChanges(false);
-            setIsDragOver(false); // Clear drag state when file loads successfully
-            console.log('File loaded from event:', filePath);
-            
-            // Start watching the file for changes
-            startFileWatcher(filePath);
-          } else {
-            console.log('Event payload format unexpected:', event.payload);
-          }
-        });
+        // Tabs open, close and come to the front as the backend says; it keeps the
+        // tab order and which tab is in front for the whole app
+        const tabListeners = await Promise.all([
+          currentWindow.listen<PendingTab>('tab-opened', (event) => {
+            console.log('Tab opened:', event.payload.id);
+            setIsDragOver(false); // Clear drag state when a file loads successfully
+            receiveTab(event.payload);
+          }),
+          currentWindow.listen<string>('tab-activate', (event) => {
+            showTab(event.payload);
+          }),
+          currentWindow.listen<string>('tab-closed', (event) => {
+            console.log('Tab closed:', event.payload);
+            updateTabs(list => list.filter(tab => tab.id !== event.payload));
+            closedTabsRef.current.push(event.payload);
+            disposeClosedModels();
+          }),
+        ]);
         
         // Set up file change listener for automatic reloading (window-specific)
-        const fileChangedListener = await currentWindow.listen<[string, string]>('file-changed-externally', (event) => {
+        const fileChangedListener = await currentWindow.listen<[string, ExternalContent]>('file-changed-externally', (event) => {
           console.log('File changed externally:', event);
           if (Array.isArray(event.payload) && event.payload.length === 2) {
-            const [filePath, newContent] = event.payload;
+            const [filePath, change] = event.payload;
             
             // Use refs to avoid stale closure issues
             const currentHasUnsavedChanges = hasUnsavedChangesRef.current;
@@ -126,14 +395,111 @@ function App() {
             // Only auto-reload if user hasn't made changes
             if (!currentHasUnsavedChanges && filePath === currentFileFromRef) {
               console.log('Auto-reloading file content');
-              setContent(newContent);
-            } else if (currentHasUnsavedChanges) {
-              console.log('File changed externally but user has unsaved changes - not auto-reloading');
-              // Could show a notification here
+              applyExternalContent(change);
+              // The reloaded disk version is now the baseline for save conflict detection
+              acknowledgeExternalChange(activeTabRef.current, filePath);
+            } else if (currentHasUnsavedChanges && filePath === currentFileFromRef) {
+              console.log('File changed externally but user has unsaved changes - offering a merge');
+              if (confirm('This file was changed on disk by another program.\n\nMerge those changes into your unsaved edits?')) {
+                mergeExternalChange(filePath);
+              }
+            } else {
+              const tab = backgroundTabShowing(filePath);
+              if (!tab) return;
+              if (!tab.state.hasUnsavedChanges) {
+                console.log('Reloading background tab', tab.id);
+                updateTabState(tab.id, state => ({
+                  content: change.kind === 'full' ? change.content : applyLineEdits(state.content, change.edits),
+                }));
+                acknowledgeExternalChange(tab.id, filePath);
+              } else {
+                // Asked about once the user comes back to the tab
+                updateTabState(tab.id, () => ({ changedOnDisk: true }));
+              }
             }
           }
         });
         
+        // The watcher follows the file across renames and reports when it disappears or returns
+        const fileLifecycleListeners = await Promise.all([
+          currentWindow.listen<string>('file-deleted', (event) => {
+            if (event.payload === currentFileRef.current) {
+              console.log('Watched file was deleted:', event.payload);
+              setIsOrphaned(true);
+            } else {
+              const tab = backgroundTabShowing(event.payload);
+              if (tab) updateTabState(tab.id, () => ({ isOrphaned: true }));
+            }
+          }),
+          currentWindow.listen<[string, string]>('file-renamed', (event) => {
+            const [oldPath, newPath] = event.payload;
+            if (oldPath === currentFileRef.current) {
+              console.log('Watched file was renamed:', oldPath, '->', newPath);
+              setCurrentFile(newPath);
+              setIsOrphaned(false);
+            } else {
+              const tab = backgroundTabShowing(oldPath);
+              if (tab) updateTabState(tab.id, () => ({ currentFile: newPath, isOrphaned: false }));
+            }
+          }),
+          currentWindow.listen<string[]>('asset-changed', (event) => {
+            console.log('Linked assets changed:', event.payload);
+            setAssetVersion(version => version + 1);
+          }),
+          currentWindow.listen<[string, string]>('file-restored', (event) => {
+            const [filePath, newContent] = event.payload;
+            if (filePath !== currentFileRef.current) {
+              const tab = backgroundTabShowing(filePath);
+              if (!tab) return;
+              updateTabState(tab.id, state => state.hasUnsavedChanges
+                ? { isOrphaned: false }
+                : { isOrphaned: false, content: newContent });
+              if (!tab.state.hasUnsavedChanges) {
+                acknowledgeExternalChange(tab.id, filePath);
+              }
+              return;
+            }
+            console.log('Watched file is back:', filePath);
+            setIsOrphaned(false);
+            if (!hasUnsavedChangesRef.current) {
+              setContent(newContent);
+              acknowledgeExternalChange(activeTabRef.current, filePath);
+            }
+          }),
+        ]);
+
+        // Large files arrive as a notice, then stream in chunks once the user agrees
+        const largeFileListeners = await Promise.all([
+          currentWindow.listen<OpenProgress>('file-open-progress', (event) => {
+            const { path, loaded, total, chunk } = event.payload;
+            openChunksRef.current.push(chunk);
+            setOpenProgress({ path, loaded, total });
+          }),
+          currentWindow.listen<[string, string, DocumentInfo]>('file-open-complete', (event) => {
+            const [documentId, filePath, info] = event.payload;
+            const fileContent = openChunksRef.current.join('');
+            openChunksRef.current = [];
+            setOpenProgress(null);
+            const state: TabState = {
+              ...emptyTabState(),
+              content: fileContent,
+              currentFile: filePath,
+              readOnly: info.readOnly,
+              view: info.view,
+            };
+            if (documentId === activeTabRef.current) {
+              loadTabState(state);
+            } else {
+              updateTabState(documentId, () => state);
+            }
+            startFileWatcher(filePath);
+          }),
+          currentWindow.listen<string>('file-open-cancelled', () => {
+            openChunksRef.current = [];
+            setOpenProgress(null);
+          }),
+        ]);
+
         // Remove catch-all listener as it's causing invalid event name error
         console.log('Event listeners ready to be set up...');
         
@@ -155,6 +521,43 @@ function App() {
             console.log('Menu save as file event received');
             handleSaveAsFile();
           }),
+          currentWindow.listen<string>('save-before-close', async (event) => {
+            console.log('Save before close requested for', event.payload);
+            showTab(event.payload);
+            const saved = (await saveFileRef.current?.()) ?? false;
+            const { invoke } = await import('@tauri-apps/api/core');
+            await invoke('finish_save_before_close', { document: event.payload, saved });
+          }),
+          currentWindow.listen('menu-move-tab', async () => {
+            console.log('Menu move tab event received');
+            const { invoke } = await import('@tauri-apps/api/core');
+            await invoke('move_tab', { document: activeTabRef.current, content: contentRef.current })
+              .catch(error => console.error('Error moving tab:', error));
+          }),
+          currentWindow.listen('menu-reopen-with-encoding', () => {
+            console.log('Menu reopen with encoding event received');
+            handleReopenWithEncoding();
+          }),
+          currentWindow.listen('menu-save-with-encoding', () => {
+            console.log('Menu save with encoding event received');
+            handleSaveWithEncoding();
+          }),
+          currentWindow.listen('menu-restore-backup', () => {
+            console.log('Menu restore backup event received');
+            handleRestoreBackup();
+          }),
+          currentWindow.listen('menu-local-history', () => {
+            console.log('Menu local history event received');
+            handleLocalHistory();
+          }),
+          currentWindow.listen('menu-label-checkpoint', () => {
+            console.log('Menu label checkpoint event received');
+            handleLabelCheckpoint();
+          }),
+          currentWindow.listen<'lf' | 'crlf'>('menu-convert-line-endings', (event) => {
+            console.log('Menu convert line endings event received:', event.payload);
+            handleConvertLineEndings(event.payload);
+          }),
           currentWindow.listen('menu-about', () => {
             console.log('Menu about event received');
             setShowAbout(true);
@@ -224,9 +627,22 @@ function App() {
             console.log('Tauri drag-leave event received:', event);
             setIsDragOver(false);
           }),
-          currentWindow.listen<string>('file-saved', (event) => {
-            setCurrentFile(event.payload);
+          currentWindow.listen<{ operation: string; error: AppError }>('file-error', (event) => {
+            const { operation, error } = event.payload;
+            console.error(`File ${operation} failed:`, error);
+            setIsDragOver(false);
+            alert(`Could not ${operation} ${error.path ?? 'file'}:\n${error.message}`);
+          }),
+          currentWindow.listen<[string, string]>('file-saved', (event) => {
+            const [documentId, filePath] = event.payload;
+            if (documentId !== activeTabRef.current) {
+              updateTabState(documentId, () => ({ currentFile: filePath, hasUnsavedChanges: false, readOnly: null, isOrphaned: false }));
+              return;
+            }
+            setCurrentFile(filePath);
             setHasUnsavedChanges(false);
+            setReadOnly(null);
+            setIsOrphaned(false);
           }),
           currentWindow.listen<void>('file-new', () => {
             handleNewFile();
@@ -235,8 +651,10 @@ function App() {
 
         unlisten = () => {
           unlistenFns.forEach(fn => fn());
-          fileOpenedListener();
+          tabListeners.forEach(fn => fn());
           fileChangedListener();
+          fileLifecycleListeners.forEach(fn => fn());
+          largeFileListeners.forEach(fn => fn());
         };
 
         console.log('All Tauri event listeners set up successfully');
@@ -249,23 +667,30 @@ function App() {
       console.log('About to setup listeners...');
       await setupListeners();
 
-      // Signal to the backend that this window is ready and retrieve any file
-      // that was queued before the frontend finished initializing (e.g. a file
-      // double-clicked in Finder on a cold start).
+      // Signal to the backend that this window is ready and retrieve the tabs
+      // that were queued before the frontend finished initializing (e.g. a file
+      // double-clicked in Finder on a cold start, or a window restored from the last session).
       try {
         const { invoke } = await import('@tauri-apps/api/core');
-        const pendingFile = await invoke<[string, string] | null>('window_ready');
-        if (Array.isArray(pendingFile) && pendingFile.length === 2) {
-          const [filePath, fileContent] = pendingFile;
-          console.log('Loading pending file from backend:', filePath);
-          setCurrentFile(filePath);
-          setContent(fileContent);
-          setHasUnsavedChanges(false);
-          startFileWatcher(filePath);
-        }
+        const ready = await invoke<ReadyTabs>('window_ready');
+        console.log('Loading', ready.tabs.length, 'pending tab(s) from backend');
+        // A window restored from the last session gets the reading mode, zoom,
+        // split, scroll and cursor of each tab back
+        const received = await Promise.all(ready.tabs.map(async (tab) => {
+          const state = tabStateFor(tab.open);
+          const view = await invoke<ViewState | null>('get_view_state', { document: tab.id }).catch(() => null);
+          return { id: tab.id, state: view ? { ...state, view } : state };
+        }));
+        updateTabs(() => received);
+        received.forEach(tab => {
+          if (tab.state.currentFile) startFileWatcher(tab.state.currentFile);
+        });
+        const front = ready.active ?? received[0]?.id;
+        if (front) showTab(front);
       } catch (error) {
         console.error('Error calling window_ready:', error);
       }
+      viewLoadedRef.current = true;
     };
 
     initializeApp();
@@ -303,26 +728,170 @@ function App() {
     }
   }, [isDarkTheme, isTauri])
 
-  const handleNewFile = async () => {
-    // Stop watching the current file if any
-    if (currentFile) {
-      await stopFileWatcher(currentFile)
+  // Change the tab list, keeping the ref in step for handlers registered once at startup
+  const updateTabs = (change: (list: Tab[]) => Tab[]) => {
+    tabsRef.current = change(tabsRef.current)
+    setTabs(tabsRef.current)
+  }
+
+  // Change what a tab in the background keeps
+  const updateTabState = (id: string, change: (state: TabState) => Partial<TabState>) => {
+    updateTabs(list => list.map(tab => tab.id === id ? { ...tab, state: { ...tab.state, ...change(tab.state) } } : tab))
+  }
+
+  // The tab in the background that shows `filePath`, if any
+  const backgroundTabShowing = (filePath: string): Tab | undefined =>
+    tabsRef.current.find(tab => tab.id !== activeTabRef.current && tab.state.currentFile === filePath)
+
+  // Show a tab's state in the editor. The refs are set right away, so a save or
+  // merge that follows in the same handler works on the tab just shown.
+  const loadTabState = (state: TabState) => {
+    contentRef.current = state.content
+    currentFileRef.current = state.currentFile
+    hasUnsavedChangesRef.current = state.hasUnsavedChanges
+    setContent(state.content)
+    setCurrentFile(state.currentFile)
+    setHasUnsavedChanges(state.hasUnsavedChanges)
+    setReadOnly(state.readOnly)
+    setIsOrphaned(state.isOrphaned)
+    setWatchInfo(state.watchInfo)
+    setMergeConflicts(state.mergeConflicts)
+    pendingViewRef.current = null
+    lastViewRef.current = state.view
+    if (state.view) {
+      restoreView(state.view)
     }
-    
+    if (state.largeFile) {
+      handleLargeFileNotice(state.largeFile, activeTabRef.current)
+    }
+  }
+
+  // Put the tab in front away with its editor state, and bring tab `id` to the front
+  const showTab = (id: string) => {
+    const outgoing = activeTabRef.current
+    const incoming = tabsRef.current.find(tab => tab.id === id)
+    if (id === outgoing || !incoming) return
+    if (outgoing && tabsRef.current.some(tab => tab.id === outgoing)) {
+      const state: TabState = { ...frontTabRef.current, view: currentView() }
+      updateTabState(outgoing, () => state)
+      // The debounced reports only ever go out for the tab in front, so send the outgoing tab's now
+      import('@tauri-apps/api/core').then(({ invoke }) => {
+        if (viewLoadedRef.current) {
+          invoke('update_view_state', { document: outgoing, view: state.view }).catch(() => {})
+        }
+        if (state.hasUnsavedChanges) {
+          invoke('update_recovery_buffer', { document: outgoing, path: state.currentFile, content: state.content }).catch(() => {})
+        }
+      }).catch(() => {})
+    }
+    activeTabRef.current = id
+    setActiveTab(id)
+    loadTabState(incoming.state)
+    if (incoming.state.changedOnDisk && incoming.state.currentFile) {
+      const filePath = incoming.state.currentFile
+      // Ask once the tab is on screen
+      setTimeout(() => {
+        if (confirm('This file was changed on disk by another program while its tab was in the background.\n\nMerge those changes into your unsaved edits?')) {
+          mergeExternalChange(filePath)
+        }
+      }, 0)
+    }
+  }
+
+  // Take in a tab the backend opened, or a document it loaded into one the window
+  // has, and bring it to the front
+  const receiveTab = (tab: PendingTab) => {
+    const state = tabStateFor(tab.open)
+    if (tabsRef.current.some(existing => existing.id === tab.id)) {
+      updateTabState(tab.id, () => state.largeFile ? { largeFile: state.largeFile } : state)
+    } else {
+      // New tabs go right after the one in front, where the backend puts them too
+      updateTabs(list => {
+        const index = list.findIndex(existing => existing.id === activeTabRef.current) + 1
+        return [...list.slice(0, index), { id: tab.id, state }, ...list.slice(index)]
+      })
+    }
+    if (state.currentFile) {
+      startFileWatcher(state.currentFile)
+    }
+    if (tab.id !== activeTabRef.current) {
+      showTab(tab.id)
+    } else if (state.largeFile) {
+      // The document in front stays until the large file has streamed in
+      handleLargeFileNotice(state.largeFile, tab.id)
+    } else {
+      loadTabState(state)
+    }
+  }
+
+  // Dispose the editor models of closed tabs, which hold their text and undo history
+  const disposeClosedModels = () => {
+    closedTabsRef.current = closedTabsRef.current.filter(id => {
+      if (id === shownTabRef.current) return true
+      disposeEditorModel(id)
+      return false
+    })
+  }
+
+  // The user picked a tab in the tab strip
+  const selectTab = async (id: string) => {
+    showTab(id)
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('activate_tab', { document: id })
+    } catch (error) {
+      console.error('Error activating tab:', error)
+    }
+  }
+
+  // Close a tab; the backend asks about unsaved changes first
+  const closeTab = async (id: string) => {
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('close_tab', { document: id })
+    } catch (error) {
+      console.error('Error closing tab:', error)
+    }
+  }
+
+  const handleNewTab = async () => {
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('new_tab')
+    } catch (error) {
+      console.error('Error opening new tab:', error)
+    }
+  }
+
+  // Dragging a tab over another moves it to that place. The new order is sent to
+  // the backend when the tab is let go.
+  const dragTabOver = (id: string) => {
+    const dragged = draggedTabRef.current
+    if (!dragged || dragged === id) return
+    updateTabs(list => {
+      const from = list.findIndex(tab => tab.id === dragged)
+      const to = list.findIndex(tab => tab.id === id)
+      if (from === -1 || to === -1) return list
+      const next = [...list]
+      const [moved] = next.splice(from, 1)
+      next.splice(to, 0, moved)
+      return next
+    })
+    tabsReorderedRef.current = true
+  }
+
+  const handleNewFile = async () => {
     setContent('')
     setCurrentFile(null)
     setHasUnsavedChanges(false)
+    setReadOnly(null)
+    setIsOrphaned(false)
     console.log('New file created')
   }
 
   const openFile = async () => {
     console.log('openFile called, isTauri:', isTauri);
     
-    // Stop watching the current file if any
-    if (currentFile) {
-      await stopFileWatcher(currentFile)
-    }
-    
     if (!isTauri) {
       // Web mode - create file input element
       const input = document.createElement('input');
@@ -386,40 +955,130 @@ function App() {
 
   
 
-  const handleSaveFile = async () => {
+  // Save to the current file, or ask for one. Resolves to whether the buffer was saved.
+  const handleSaveFile = async (overwrite = false): Promise<boolean> => {
     if (isTauri) {
       // Use Tauri save
       try {
         const { invoke } = await import('@tauri-apps/api/core')
-        if (currentFile) {
-          await invoke('save_file', { path: currentFile, content })
+        // Read through the refs: the backend may have just brought this tab to the front
+        const filePath = currentFileRef.current
+        if (filePath) {
+          await invoke('save_file', { document: activeTabRef.current, path: filePath, content: contentRef.current, overwrite })
           setHasUnsavedChanges(false)
           console.log('File saved successfully')
+          return true
         } else {
-          await handleSaveAsFile()
+          return await handleSaveAsFile()
         }
       } catch (error) {
+        const saveError = error as AppError
+        if (saveError?.kind === 'conflict') {
+          // Someone else changed the file since we loaded it
+          if (confirm('This file was changed on disk by another program.\n\nOverwrite it with your version?')) {
+            return await handleSaveFile(true)
+          } else if (confirm('Save your version as a new file instead?')) {
+            return await handleSaveAsFile()
+          }
+          return false
+        }
+        if (saveError?.kind === 'readOnly') {
+          if (confirm(`${saveError.message}.\n\nSave your version as a new file?`)) {
+            return await handleSaveAsFile()
+          } else if (confirm('Keep editing it as an untitled copy instead?')) {
+            await handleOpenAsEditableCopy()
+          }
+          return false
+        }
         console.error('Error saving file:', error)
+        return false
       }
     } else {
       // Use download for web
-      handleSaveAsFile()
+      return handleSaveAsFile()
     }
   }
+  saveFileRef.current = handleSaveFile
 
-  const handleSaveAsFile = async () => {
+  // Show a file changed on disk in the clean buffer. Edits go straight to the editor
+  // model, so the cursor, scroll position and undo history survive the reload.
+  const applyExternalContent = (change: ExternalContent) => {
+    if (change.kind === 'full') {
+      setContent(change.content)
+      return
+    }
+    // The editor may still hold the model of the tab that was in front
+    const model = shownTabRef.current === activeTabRef.current ? editorRef.current?.getModel() : null
+    if (!model) {
+      setContent(applyLineEdits(contentRef.current, change.edits))
+      return
+    }
+    model.pushEditOperations([], change.edits.map(edit => ({
+      range: new monaco.Range(edit.startLine, 1, edit.startLine + edit.deletedLines, 1),
+      text: edit.text,
+    })), () => null)
+    // The model change comes back through onChange, but this is the disk version
+    setContent(model.getValue())
+    setHasUnsavedChanges(false)
+  }
+
+  // Merge a file changed on disk into the unsaved buffer. The result stays unsaved,
+  // and the disk version becomes the base for the next merge or save.
+  const mergeExternalChange = async (filePath: string) => {
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      const documentId = activeTabRef.current
+      const outcome = await invoke<MergeOutcome>('merge_external_change', { document: documentId, path: filePath, content: contentRef.current })
+      setContent(outcome.content)
+      setHasUnsavedChanges(true)
+      setMergeConflicts(outcome.conflicts)
+      await invoke('acknowledge_external_change', { document: documentId, path: filePath })
+      if (outcome.conflicts.length > 0) {
+        alert(`${outcome.conflicts.length} change(s) overlap with your edits and are marked in the editor.\nKeep the version you want between the <<<<<<< and >>>>>>> lines.`)
+      }
+    } catch (error) {
+      console.error('Error merging external change:', error)
+    }
+  }
+
+  // Make the disk version of a file the baseline for the document's next save conflict check
+  const acknowledgeExternalChange = (documentId: string | null, filePath: string) => {
+    import('@tauri-apps/api/core').then(({ invoke }) => {
+      invoke('acknowledge_external_change', { document: documentId, path: filePath }).catch(() => {})
+    }).catch(() => {})
+  }
+
+  // Detach a read-only document from its file so it can be edited and saved elsewhere
+  const handleOpenAsEditableCopy = async () => {
+    if (!isTauri) return
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('open_as_editable_copy', { document: activeTabRef.current })
+      setCurrentFile(null)
+      setReadOnly(null)
+      setIsOrphaned(false)
+      setHasUnsavedChanges(true)
+    } catch (error) {
+      console.error('Error opening editable copy:', error)
+    }
+  }
+
+  const handleSaveAsFile = async (): Promise<boolean> => {
     if (isTauri) {
       // Use Tauri save dialog
       try {
         const { invoke } = await import('@tauri-apps/api/core')
-        const result = await invoke<string | null>('save_file_dialog', { content })
+        const result = await invoke<string | null>('save_file_dialog', { document: activeTabRef.current, content: contentRef.current })
         if (result) {
           setCurrentFile(result)
           setHasUnsavedChanges(false)
           console.log('File saved as:', result)
+          return true
         }
+        return false
       } catch (error) {
         console.error('Error saving file as:', error)
+        return false
       }
     } else {
       // Use download for web
@@ -434,6 +1093,132 @@ function App() {
       URL.revokeObjectURL(url)
       setHasUnsavedChanges(false)
       console.log('File downloaded:', currentFile || 'document.md')
+      return true
+    }
+  }
+
+  // Ask for an encoding name from the list the backend supports
+  const promptForEncoding = async (title: string): Promise<string | null> => {
+    const { invoke } = await import('@tauri-apps/api/core')
+    const encodings = await invoke<string[]>('list_encodings')
+    const choice = prompt(`${title}\n\nAvailable: ${encodings.join(', ')}`, encodings[0])
+    return choice?.trim() || null
+  }
+
+  const handleReopenWithEncoding = async () => {
+    const filePath = currentFileRef.current
+    if (!isTauri || !filePath) return
+    if (hasUnsavedChangesRef.current && !confirm('Reopening discards your unsaved changes. Continue?')) return
+    try {
+      const encoding = await promptForEncoding('Reopen with encoding:')
+      if (!encoding) return
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('reopen_with_encoding', { document: activeTabRef.current, path: filePath, encoding })
+    } catch (error) {
+      console.error('Error reopening with encoding:', error)
+    }
+  }
+
+  const handleSaveWithEncoding = async () => {
+    const filePath = currentFileRef.current
+    if (!isTauri || !filePath) return
+    try {
+      const encoding = await promptForEncoding('Save with encoding:')
+      if (!encoding) return
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('save_with_encoding', { document: activeTabRef.current, path: filePath, content: contentRef.current, encoding })
+      setHasUnsavedChanges(false)
+    } catch (error) {
+      console.error('Error saving with encoding:', error)
+    }
+  }
+
+  const handleRestoreBackup = async () => {
+    const filePath = currentFileRef.current
+    if (!isTauri || !filePath) return
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      const backups = await invoke<BackupInfo[]>('list_backups', { path: filePath })
+      if (backups.length === 0) {
+        alert('There are no backups of this file.')
+        return
+      }
+      const choices = backups.map((backup, i) => {
+        const when = backup.modifiedMs ? new Date(backup.modifiedMs).toLocaleString() : 'unknown date'
+        return `${i + 1}. ${when} (${backup.size} bytes)`
+      })
+      const choice = prompt(`Restore which backup?\n\n${choices.join('\n')}`, '1')
+      const backup = backups[Number(choice) - 1]
+      if (!backup) return
+      const restored = await invoke<string>('restore_backup', { document: activeTabRef.current, path: filePath, backup: backup.path })
+      setContent(restored)
+      setHasUnsavedChanges(true)
+    } catch (error) {
+      console.error('Error restoring backup:', error)
+    }
+  }
+
+  const handleLocalHistory = async () => {
+    const filePath = currentFileRef.current
+    if (!isTauri || !filePath) return
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      const snapshots = await invoke<Snapshot[]>('list_history', { path: filePath })
+      if (snapshots.length === 0) {
+        alert('There is no local history for this file yet.')
+        return
+      }
+      const choices = snapshots.map((snapshot, i) => {
+        const when = new Date(snapshot.timestampMs).toLocaleString()
+        const label = snapshot.label ? ` "${snapshot.label}"` : ''
+        return `${i + 1}. ${when}${label} (${snapshot.source}, ${snapshot.size} bytes)`
+      })
+      const choice = prompt(`Local history:\n\n${choices.join('\n')}\n\nCompare which version with the editor?`, '1')
+      const snapshot = snapshots[Number(choice) - 1]
+      if (!snapshot) return
+      const diff = await invoke<string>('diff_snapshot_with_buffer', {
+        path: filePath,
+        snapshot: snapshot.id,
+        content: contentRef.current,
+      })
+      if (!confirm(`${diff}\n\nRestore this version?`)) return
+      const restored = await invoke<string>('restore_snapshot', { path: filePath, snapshot: snapshot.id })
+      setContent(restored)
+      setHasUnsavedChanges(true)
+    } catch (error) {
+      console.error('Error showing local history:', error)
+    }
+  }
+
+  const handleLabelCheckpoint = async () => {
+    const filePath = currentFileRef.current
+    if (!isTauri || !filePath) return
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      const [latest] = await invoke<Snapshot[]>('list_history', { path: filePath })
+      if (!latest) {
+        alert('Save the file first to create a checkpoint.')
+        return
+      }
+      const label = prompt('Checkpoint name for the last saved version:', latest.label ?? '')
+      if (label === null) return
+      await invoke('label_snapshot', { path: filePath, snapshot: latest.id, label })
+    } catch (error) {
+      console.error('Error labeling checkpoint:', error)
+    }
+  }
+
+  const handleConvertLineEndings = async (lineEnding: 'lf' | 'crlf') => {
+    if (!isTauri) return
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      const converted = await invoke<string>('convert_line_endings', { document: activeTabRef.current, content: contentRef.current, lineEnding })
+      if (converted !== contentRef.current) {
+        setContent(converted)
+        setHasUnsavedChanges(true)
+      }
+    } catch (error) {
+      console.error('Error converting line endings:', error)
     }
   }
 
@@ -469,11 +1254,73 @@ function App() {
     console.log('Theme toggled to:', newTheme ? 'dark' : 'light')
   }
 
+  // How the tab in front is shown right now
+  const currentView = (): ViewState => {
+    // The editor is unmounted in reading mode, may still hold the model of the tab that
+    // was in front, and a restored position may still be waiting for it; the saved
+    // cursor and scroll position count until then
+    const editor = pendingViewRef.current || !editorRef.current?.getModel() || shownTabRef.current !== activeTabRef.current
+      ? null : editorRef.current
+    const position = editor?.getPosition()
+    const saved = pendingViewRef.current ?? lastViewRef.current
+    return {
+      readingMode: isReadingModeRef.current,
+      zoom: parseFloat(document.body.style.zoom || '1'),
+      splitRatio: splitRatioRef.current,
+      scrollTop: editor ? editor.getScrollTop() : saved?.scrollTop ?? 0,
+      cursorLine: position?.lineNumber ?? saved?.cursorLine ?? 1,
+      cursorColumn: position?.column ?? saved?.cursorColumn ?? 1,
+    }
+  }
+
+  // Tell the backend how the window shows the document in front, for session restore and
+  // for reopening the document. Reports are debounced, since scrolling produces a stream of them.
+  const reportViewState = () => {
+    if (!viewLoadedRef.current) return
+    if (viewReportTimerRef.current !== null) clearTimeout(viewReportTimerRef.current)
+    viewReportTimerRef.current = window.setTimeout(async () => {
+      viewReportTimerRef.current = null
+      if (!activeTabRef.current) return
+      const view = currentView()
+      lastViewRef.current = view
+      try {
+        const { invoke } = await import('@tauri-apps/api/core')
+        await invoke('update_view_state', { document: activeTabRef.current, view })
+      } catch (error) {
+        console.error('Error reporting view state:', error)
+      }
+    }, 500)
+  }
+
+  // Show the document the way it was shown before: reading mode, zoom and split
+  // right away, the cursor and scroll position once the editor has the content
+  const restoreView = (view: ViewState) => {
+    setIsReadingMode(view.readingMode)
+    document.body.style.zoom = view.zoom.toString()
+    setSplitRatio(view.splitRatio)
+    pendingViewRef.current = view
+    applyPendingView()
+  }
+
+  // Put a restored cursor and scroll position back once the editor holds the tab's
+  // model with enough of the document. Large files stream in, so this runs as content arrives.
+  const applyPendingView = () => {
+    const view = pendingViewRef.current
+    const editor = editorRef.current
+    const model = editor?.getModel()
+    if (shownTabRef.current !== activeTabRef.current) return
+    if (!view || !editor || !model || model.getLineCount() < view.cursorLine) return
+    pendingViewRef.current = null
+    editor.setPosition({ lineNumber: view.cursorLine, column: view.cursorColumn })
+    editor.setScrollTop(view.scrollTop)
+  }
+
   const handleZoomIn = () => {
     const currentZoom = parseFloat(document.body.style.zoom || '1')
     const newZoom = Math.min(currentZoom + 0.1, 2.0)
     document.body.style.zoom = newZoom.toString()
     console.log(`Zoomed in to ${Math.round(newZoom * 100)}%`)
+    reportViewState()
   }
 
   const handleZoomOut = () => {
@@ -481,11 +1328,13 @@ function App() {
     const newZoom = Math.max(currentZoom - 0.1, 0.5)
     document.body.style.zoom = newZoom.toString()
     console.log(`Zoomed out to ${Math.round(newZoom * 100)}%`)
+    reportViewState()
   }
 
   const handleResetZoom = () => {
     document.body.style.zoom = '1'
     console.log('Zoom reset to 100%')
+    reportViewState()
   }
 
   // Menu-triggered editor operations (keyboard shortcuts handled by Monaco)
@@ -554,12 +1403,50 @@ function App() {
     }
   }
 
+  // Ask how to open a huge file, then stream it in chunks into the tab `documentId`
+  const handleLargeFileNotice = async (notice: LargeFileNotice, documentId: string | null) => {
+    if (!documentId) return
+    const megabytes = (bytes: number) => Math.round(bytes / (1024 * 1024))
+    let preview = false
+    if (notice.warn) {
+      const name = notice.path.split(/[\\/]/).pop()
+      if (!confirm(`${name} is ${megabytes(notice.size)} MB. Opening it completely may be slow.\n\nOpen the whole file?`)) {
+        if (!confirm(`View only the first ${megabytes(notice.previewBytes)} MB, read-only?`)) return
+        preview = true
+      }
+    }
+    openChunksRef.current = []
+    setOpenProgress({ path: notice.path, loaded: 0, total: preview ? notice.previewBytes : notice.size })
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('open_file_chunked', { document: documentId, path: notice.path, preview, readOnly: notice.readOnly })
+    } catch (error) {
+      setOpenProgress(null)
+      console.error('Error opening large file:', error)
+    }
+  }
+
+  const handleCancelOpen = async () => {
+    try {
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('cancel_file_open')
+    } catch (error) {
+      console.error('Error cancelling file open:', error)
+    }
+  }
+
   const startFileWatcher = async (filePath: string) => {
     if (isWeb) return
     try {
       const { invoke } = await import('@tauri-apps/api/core')
-      await invoke('start_file_watcher', { filePath })
-      console.log('Started file watcher for:', filePath)
+      const info = await invoke<WatchInfo>('start_file_watcher', { filePath })
+      if (filePath === currentFileRef.current) {
+        setWatchInfo(info)
+      } else {
+        const tab = backgroundTabShowing(filePath)
+        if (tab) updateTabState(tab.id, () => ({ watchInfo: info }))
+      }
+      console.log('Started file watcher for:', filePath, info)
     } catch (error) {
       console.error('Error starting file watcher:', error)
     }
@@ -570,6 +1457,9 @@ function App() {
     try {
       const { invoke } = await import('@tauri-apps/api/core')
       await invoke('stop_file_watcher', { filePath })
+      if (filePath === currentFileRef.current) {
+        setWatchInfo(null)
+      }
       console.log('Stopped file watcher for:', filePath)
     } catch (error) {
       console.error('Error stopping file watcher:', error)
@@ -597,7 +1487,7 @@ function App() {
 
   // Update window title when file changes (Tauri only)
   const updateWindowTitle = async (filePath: string | null, hasChanges: boolean) => {
-    const displayTitle = documentTitle || (filePath ? filePath.split('/').pop() : 'Untitled Document')
+    const displayTitle = documentTitle || (filePath ? tabName(filePath) : 'Untitled Document')
     const changeIndicator = hasChanges ? ' •' : ''
     const fullTitle = `${displayTitle}${changeIndicator} - Mark-us-Down`
 
@@ -627,10 +1517,48 @@ function App() {
     updateWindowTitle(currentFile, hasUnsavedChanges)
   }, [currentFile, hasUnsavedChanges, documentTitle])
 
+  // Highlight conflicts left by a merge: the user's lines, the disk's lines and the markers.
+  // Decorations move with edits, so they follow the text until the document changes.
+  useEffect(() => {
+    const editor = editorRef.current
+    if (!editor) return
+    const line = (startLine: number, endLine: number, className: string) => ({
+      range: new monaco.Range(startLine, 1, endLine, 1),
+      options: { isWholeLine: true, className },
+    })
+    const decorations = mergeConflicts.flatMap(conflict => [
+      line(conflict.startLine, conflict.startLine, 'merge-conflict-marker'),
+      ...(conflict.separatorLine - conflict.startLine > 1
+        ? [line(conflict.startLine + 1, conflict.separatorLine - 1, 'merge-conflict-ours')] : []),
+      line(conflict.separatorLine, conflict.separatorLine, 'merge-conflict-marker'),
+      ...(conflict.endLine - conflict.separatorLine > 1
+        ? [line(conflict.separatorLine + 1, conflict.endLine - 1, 'merge-conflict-theirs')] : []),
+      line(conflict.endLine, conflict.endLine, 'merge-conflict-marker'),
+    ])
+    const collection = editor.createDecorationsCollection(decorations)
+    if (decorations.length > 0) {
+      editor.revealLineInCenter(mergeConflicts[0].startLine)
+    }
+    return () => collection.clear()
+  }, [mergeConflicts])
+
+  // Conflict highlights belong to the document they were merged into. A tab coming
+  // to the front brings its own.
+  const conflictsTabRef = useRef(activeTab)
+  useEffect(() => {
+    if (conflictsTabRef.current === activeTab) {
+      setMergeConflicts([])
+    }
+    conflictsTabRef.current = activeTab
+  }, [currentFile, activeTab])
+
   // Scroll synchronization handlers
   const handleEditorMount = useCallback((editor: monaco.editor.IStandaloneCodeEditor) => {
     scrollSyncService.setEditor(editor)
     editorRef.current = editor // Store reference for clipboard operations
+    editor.onDidScrollChange(() => reportViewState())
+    editor.onDidChangeCursorPosition(() => reportViewState())
+    applyPendingView()
   }, [])
 
   const handlePreviewMount = useCallback((element: HTMLDivElement) => {
@@ -645,6 +1573,34 @@ function App() {
     scrollSyncService.syncPreviewToEditor()
   }
 
+  // The editor has switched to the model of the tab in front by now, since its
+  // effects run before these
+  useEffect(() => {
+    shownTabRef.current = activeTab
+    disposeClosedModels()
+  }, [activeTab])
+
+  // A restored view waits for its lines to reach the editor
+  useEffect(() => {
+    applyPendingView()
+  }, [content, activeTab])
+
+  // The new tab order goes to the backend when a dragged tab is let go
+  useEffect(() => {
+    const finishTabDrag = () => {
+      if (tabsReorderedRef.current) {
+        const order = tabsRef.current.map(tab => tab.id)
+        import('@tauri-apps/api/core').then(({ invoke }) => {
+          invoke('reorder_tabs', { order }).catch(error => console.error('Error reordering tabs:', error))
+        }).catch(() => {})
+      }
+      draggedTabRef.current = null
+      tabsReorderedRef.current = false
+    }
+    window.addEventListener('pointerup', finishTabDrag)
+    return () => window.removeEventListener('pointerup', finishTabDrag)
+  }, [])
+
   // Rebuild line mappings whenever content changes
   useEffect(() => {
     // Give the preview time to render
@@ -748,28 +1704,69 @@ function App() {
     }
   }, [])
 
-  // Separate effect for file watcher cleanup
+  // Stop watching files that no tab shows any more, as tabs close or change files
+  const tabFilesRef = useRef<Set<string>>(new Set())
   useEffect(() => {
-    return () => {
-      // Stop file watcher when currentFile changes
-      if (currentFile) {
-        stopFileWatcher(currentFile)
-      }
-    }
-  }, [currentFile])
+    const files = new Set<string>()
+    tabs.forEach(tab => {
+      if (tab.id !== activeTab && tab.state.currentFile) files.add(tab.state.currentFile)
+    })
+    if (currentFile) files.add(currentFile)
+    tabFilesRef.current.forEach(file => {
+      if (!files.has(file)) stopFileWatcher(file)
+    })
+    tabFilesRef.current = files
+  }, [tabs, activeTab, currentFile])
 
   // Report window empty state to backend so it knows whether to reuse this window
   // when opening files via Finder double-click
   const isWindowEmptyRef = useRef<boolean | null>(null)
   useEffect(() => {
-    const isEmpty = currentFile === null && content === ''
+    if (!activeTab) return
+    const isEmpty = tabs.length <= 1 && currentFile === null && content === ''
     if (isWindowEmptyRef.current === isEmpty) return
     isWindowEmptyRef.current = isEmpty
     import('@tauri-apps/api/core').then(({ invoke }) => {
-      invoke('set_window_empty', { isEmpty }).catch(() => {})
+      invoke('set_window_empty', { document: activeTab, isEmpty }).catch(() => {})
+    }).catch(() => {})
+  }, [currentFile, content, tabs.length, activeTab])
+
+  // Report unsaved changes to the backend, which asks about them before the tab
+  // or window closes or the app quits
+  useEffect(() => {
+    if (!isTauri || !activeTab) return
+    import('@tauri-apps/api/core').then(({ invoke }) => {
+      invoke('set_document_dirty', { document: activeTab, isDirty: hasUnsavedChanges }).catch(() => {})
     }).catch(() => {})
-  }, [currentFile, content])
+  }, [hasUnsavedChanges, activeTab, isTauri])
 
+  // Keep the backend crash-recovery journal up to date with unsaved changes.
+  // Updates are batched here and debounced again by the backend before hitting disk.
+  useEffect(() => {
+    if (!isTauri || !activeTab) return
+    const timer = setTimeout(() => {
+      import('@tauri-apps/api/core').then(({ invoke }) => {
+        if (hasUnsavedChanges) {
+          invoke('update_recovery_buffer', { document: activeTab, path: currentFile, content }).catch(() => {})
+        } else {
+          invoke('discard_recovery_buffer', { document: activeTab }).catch(() => {})
+        }
+      }).catch(() => {})
+    }, 1000)
+    return () => clearTimeout(timer)
+  }, [currentFile, content, hasUnsavedChanges, activeTab, isTauri])
+
+  // Watch the local images and files the document links to, so the preview
+  // refreshes when they are edited in another tool
+  useEffect(() => {
+    if (!isTauri) return
+    const timer = setTimeout(() => {
+      import('@tauri-apps/api/core').then(({ invoke }) => {
+        invoke('watch_assets', { path: currentFile, content }).catch(() => {})
+      }).catch(() => {})
+    }, 1000)
+    return () => clearTimeout(timer)
+  }, [currentFile, content, isTauri])
 
   return (
     <div 
@@ -785,18 +1782,42 @@ function App() {
             <span className="file-name">
               {documentTitle || 'Untitled Document'}
               {hasUnsavedChanges && ' •'}
+              {readOnly && ' (read-only)'}
+              {isOrphaned && ' (deleted)'}
             </span>
             <span className="file-path">
               {currentFile ? currentFile.split('/').pop() : 'No file loaded'}
+              {currentFile && watchInfo?.strategy === 'poll' && (
+                <span
+                  className="watch-strategy"
+                  title={`Checked for changes every ${(watchInfo.pollIntervalMs ?? 0) / 1000}s${watchInfo.filesystem ? ` (${watchInfo.filesystem})` : ''}`}
+                >
+                  {' · polling'}
+                </span>
+              )}
             </span>
           </div>
         </div>
         <div className="controls">
+          {openProgress && (
+            <>
+              <span className="open-progress">
+                Loading {openProgress.path.split(/[\\/]/).pop()}… {openProgress.total > 0 ? Math.floor(openProgress.loaded / openProgress.total * 100) : 0}%
+              </span>
+              <button onClick={handleCancelOpen}>Cancel</button>
+            </>
+          )}
+          {isTauri && readOnly && (
+            <>
+              <button onClick={handleSaveAsFile}>Save As…</button>
+              <button onClick={handleOpenAsEditableCopy}>Edit a Copy</button>
+            </>
+          )}
           {isWeb && (
             <>
               <button onClick={handleNewFile}>New</button>
               <button onClick={openFile}>Load</button>
-              <button onClick={handleSaveFile}>Save</button>
+              <button onClick={() => handleSaveFile()}>Save</button>
               <button onClick={() => setShowAbout(true)}>About</button>
               <button onClick={toggleTheme} className="theme-toggle">
                 {isDarkTheme ? '☀️' : '🌙'}
@@ -805,11 +1826,49 @@ function App() {
           )}
         </div>
       </header>
+      {tabs.length > 1 && (
+        <nav className="tab-strip">
+          {tabs.map(tab => {
+            // The tab in front shows the editor's state; the others what they kept
+            const isFront = tab.id === activeTab
+            const filePath = isFront ? currentFile : tab.state.currentFile
+            const isDirty = isFront ? hasUnsavedChanges : tab.state.hasUnsavedChanges
+            return (
+              <div
+                key={tab.id}
+                className={`tab ${isFront ? 'active' : ''}`}
+                title={filePath ?? 'Untitled'}
+                onClick={() => selectTab(tab.id)}
+                onPointerDown={() => { draggedTabRef.current = tab.id }}
+                onPointerEnter={() => dragTabOver(tab.id)}
+              >
+                <span className="tab-name">{tabName(filePath)}{isDirty && ' •'}</span>
+                <button
+                  className="tab-close"
+                  title="Close Tab"
+                  onPointerDown={(e) => e.stopPropagation()}
+                  onClick={(e) => {
+                    e.stopPropagation()
+                    closeTab(tab.id)
+                  }}
+                >
+                  ×
+                </button>
+              </div>
+            )
+          })}
+          <button className="tab-new" title="New Tab" onClick={handleNewTab}>+</button>
+        </nav>
+      )}
       <main className="editor-container">
         <SplitView
+          ratio={splitRatio}
+          onRatioChange={setSplitRatio}
           leftComponent={
             <SourceEditor
+              path={activeTab ?? undefined}
               value={content}
+              readOnly={readOnly !== null}
               onChange={handleContentChange}
               theme={isDarkTheme ? 'dark' : 'light'}
               onScroll={handleEditorScroll}
@@ -819,6 +1878,7 @@ function App() {
           rightComponent={
             <PreviewPane
               content={content}
+              assetVersion={assetVersion}
               onScroll={handlePreviewScroll}
               onMount={handlePreviewMount}
             />
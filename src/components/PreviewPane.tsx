@@ -4,13 +4,19 @@ import './PreviewPane.css'
 
 interface PreviewPaneProps {
   content: string
+  // Changes when linked files change on disk; local images are then fetched again
+  assetVersion?: number
   className?: string
   onScroll?: () => void
   onMount?: (element: HTMLDivElement) => void
 }
 
+// Local `<img src>` values, i.e. neither URLs with a scheme nor protocol-relative ones
+const LOCAL_IMG_SRC = /(<img\b[^>]*?\ssrc=")(?![a-z][a-z0-9+.-]*:|\/\/)([^"#]*)/gi
+
 export const PreviewPane: React.FC<PreviewPaneProps> = ({
   content,
+  assetVersion = 0,
   className = '',
   onScroll,
   onMount
@@ -24,12 +30,16 @@ export const PreviewPane: React.FC<PreviewPaneProps> = ({
     }
 
     try {
-      return markdownParser.parse(content)
+      const html = markdownParser.parse(content)
+      if (assetVersion === 0) return html
+      // Parsed HTML is cached, so bust the image cache on the way out instead
+      return html.replace(LOCAL_IMG_SRC, (_, prefix: string, src: string) =>
+        `${prefix}${src}${src.includes('?') ? '&' : '?'}v=${assetVersion}`)
     } catch (error) {
       console.error('Error parsing markdown:', error)
       return '<div class="preview-error">Error rendering preview</div>'
     }
-  }, [content])
+  }, [content, assetVersion])
 
   // Call onMount once when container is ready
   useEffect(() => {
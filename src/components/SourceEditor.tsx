@@ -1,9 +1,11 @@
 import React, { useRef, useCallback, useEffect } from 'react'
-import Editor, { OnMount } from '@monaco-editor/react'
+import Editor, { OnMount, loader } from '@monaco-editor/react'
 import * as monaco from 'monaco-editor'
 import './SourceEditor.css'
 
 interface SourceEditorProps {
+  // Each path gets a model of its own, with its own undo history and view state
+  path?: string
   value: string
   onChange: (value: string) => void
   readOnly?: boolean
@@ -13,7 +15,15 @@ interface SourceEditorProps {
   onEditorMount?: (editor: monaco.editor.IStandaloneCodeEditor) => void
 }
 
+// Dispose the model kept for a path that won't be shown again
+export const disposeEditorModel = (path: string) => {
+  loader.init().then(monaco => {
+    monaco.editor.getModel(monaco.Uri.parse(path))?.dispose()
+  })
+}
+
 export const SourceEditor: React.FC<SourceEditorProps> = ({
+  path,
   value,
   onChange,
   readOnly = false,
@@ -132,6 +142,7 @@ export const SourceEditor: React.FC<SourceEditorProps> = ({
       <Editor
         height="100%"
         language="markdown"
+        path={path}
         value={value}
         onChange={handleEditorChange}
         onMount={handleEditorDidMount}
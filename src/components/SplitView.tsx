@@ -6,6 +6,9 @@ interface SplitViewProps {
   rightComponent: React.ReactNode
   className?: string
   initialRatio?: number
+  // Controlled ratio, e.g. restored for a document; dragging reports through onRatioChange
+  ratio?: number
+  onRatioChange?: (ratio: number) => void
   hideLeft?: boolean
 }
 
@@ -16,12 +19,19 @@ export const SplitView: React.FC<SplitViewProps> = ({
   rightComponent,
   className = '',
   initialRatio = 0.5,
+  ratio,
+  onRatioChange,
   hideLeft = false
 }) => {
   const [leftRatio, setLeftRatio] = useState(() => {
+    if (ratio !== undefined) return ratio
     const saved = localStorage.getItem('split-view-ratio')
     return saved ? parseFloat(saved) : initialRatio
   })
+
+  useEffect(() => {
+    if (ratio !== undefined) setLeftRatio(ratio)
+  }, [ratio])
   const [isDragging, setIsDragging] = useState(false)
   const containerRef = useRef<HTMLDivElement>(null)
 
@@ -39,7 +49,8 @@ export const SplitView: React.FC<SplitViewProps> = ({
     
     setLeftRatio(newRatio)
     localStorage.setItem('split-view-ratio', newRatio.toString())
-  }, [isDragging])
+    onRatioChange?.(newRatio)
+  }, [isDragging, onRatioChange])
 
   const handleMouseUp = useCallback(() => {
     setIsDragging(false)
@@ -1,3 +1,3 @@
 export { SplitView } from './SplitView'
-export { SourceEditor } from './SourceEditor'
+export { SourceEditor, disposeEditorModel } from './SourceEditor'
 export { PreviewPane } from './PreviewPane'
\ No newline at end of file